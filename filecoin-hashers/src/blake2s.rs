@@ -304,3 +304,28 @@ impl From<Blake2sDomain> for [u8; 32] {
         val.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::HashDomain;
+
+    #[test]
+    fn test_hash2_domain() {
+        let a: Blake2sDomain = Fr::from(1u64).into();
+        let b: Blake2sDomain = Fr::from(2u64).into();
+        let tag: Blake2sDomain = Fr::from(1u64).into();
+
+        let node = Blake2sFunction::hash2_domain(&a, &b, HashDomain::Node);
+        let commitment = Blake2sFunction::hash2_domain(&a, &b, HashDomain::Commitment);
+
+        assert_eq!(node, Blake2sFunction::hash2(&a, &b));
+        assert_ne!(node, commitment);
+        // Folding the tag in with `hash2` would make the commitment a tree node.
+        assert_ne!(
+            commitment,
+            Blake2sFunction::hash2(&Blake2sFunction::hash2(&tag, &a), &b)
+        );
+    }
+}
@@ -1,6 +1,10 @@
+use std::any::Any;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::hash::{Hash as StdHash, Hasher as StdHasher};
+use std::marker::PhantomData;
 use std::panic::panic_any;
+use std::sync::Mutex;
 
 use anyhow::ensure;
 use bellperson::{
@@ -9,18 +13,23 @@ use bellperson::{
 };
 use blstrs::Scalar as Fr;
 use ff::{Field, PrimeField};
-use generic_array::typenum::{marker_traits::Unsigned, U2};
+use generic_array::typenum::{marker_traits::Unsigned, U11, U16, U2, U4, U8};
 use merkletree::{
     hash::{Algorithm as LightAlgorithm, Hashable},
     merkle::Element,
 };
-use neptune::{circuit::poseidon_hash, poseidon::Poseidon};
+use neptune::{
+    circuit::poseidon_hash,
+    poseidon::{Poseidon, PoseidonConstants},
+    Arity,
+};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
 use crate::types::{
-    Domain, HashFunction, Hasher, PoseidonArity, PoseidonMDArity, POSEIDON_CONSTANTS_16,
-    POSEIDON_CONSTANTS_2, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8, POSEIDON_MD_CONSTANTS,
+    Domain, HashDomain, HashFunction, Hasher, PoseidonArity, PoseidonMDArity,
+    POSEIDON_CONSTANTS_16, POSEIDON_CONSTANTS_2, POSEIDON_CONSTANTS_4, POSEIDON_CONSTANTS_8,
+    POSEIDON_MD_CONSTANTS,
 };
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -210,6 +219,15 @@ impl HashFunction<PoseidonDomain> for PoseidonFunction {
         fr.into()
     }
 
+    /// Poseidon's `hash` only takes whole arities, so the tag is prefixed as an extra element and
+    /// hashed with `hash_md`, whose constants differ from those `hash2` uses for tree nodes.
+    fn hash2_domain(a: &PoseidonDomain, b: &PoseidonDomain, domain: HashDomain) -> PoseidonDomain {
+        match domain {
+            HashDomain::Node => Self::hash2(a, b),
+            _ => Self::hash_md(&[Fr::from(domain.tag()).into(), *a, *b]),
+        }
+    }
+
     fn hash_md(input: &[PoseidonDomain]) -> PoseidonDomain {
         assert!(input.len() > 1, "hash_md needs more than one element.");
         let arity = PoseidonMDArity::to_usize();
@@ -374,6 +392,109 @@ impl From<PoseidonDomain> for Fr {
     }
 }
 
+/// Incrementally computes the root of an arity `U` merkle tree over column hashes, as `comm_c`
+/// is, from column hashes supplied in node order. Only the incomplete arity group at each tree
+/// level is held, rather than every column hash.
+#[derive(Debug, Clone)]
+pub struct ColumnHasher<U: PoseidonArity> {
+    /// Nodes at each height whose arity group is not yet complete.
+    levels: Vec<Vec<PoseidonDomain>>,
+    _u: PhantomData<U>,
+}
+
+impl<U: PoseidonArity> Default for ColumnHasher<U> {
+    fn default() -> Self {
+        ColumnHasher {
+            levels: Vec::new(),
+            _u: PhantomData,
+        }
+    }
+}
+
+impl<U: PoseidonArity> ColumnHasher<U> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the next column hash.
+    pub fn update(&mut self, column: PoseidonDomain) -> &mut Self {
+        let arity = U::to_usize();
+        let mut node = column;
+        let mut height = 0;
+        loop {
+            if self.levels.len() == height {
+                self.levels.push(Vec::with_capacity(arity));
+            }
+            self.levels[height].push(node);
+            if self.levels[height].len() < arity {
+                break;
+            }
+
+            let mut a = PoseidonFunction::default();
+            node = a.multi_node(&self.levels[height], height);
+            self.levels[height].clear();
+            height += 1;
+        }
+        self
+    }
+
+    /// Returns the root over all column hashes added so far, which must fill a complete tree.
+    pub fn finalize(&self) -> anyhow::Result<PoseidonDomain> {
+        let (top, below) = self
+            .levels
+            .split_last()
+            .ok_or_else(|| anyhow::anyhow!("no column hashes were added"))?;
+        ensure!(
+            !below.is_empty() && top.len() == 1 && below.iter().all(Vec::is_empty),
+            "column hashes do not fill a complete tree of arity {}",
+            U::to_usize()
+        );
+        Ok(top[0])
+    }
+}
+
+/// Generates the round constants and MDS matrices for arity `A` now, rather than on the first hash
+/// of that arity. Latency-sensitive provers, such as winning PoSt, can call this at startup.
+/// `U0`, which stands for an absent sub or top tree, has no constants and is skipped.
+pub fn warm_constants<A: PoseidonArity>() {
+    if A::to_usize() == 0 {
+        return;
+    }
+    A::PARAMETERS();
+}
+
+lazy_static::lazy_static! {
+    static ref GENERATED_CONSTANTS: Mutex<HashMap<usize, &'static (dyn Any + Send + Sync)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the Poseidon constants for arity `A`, generated on first use and cached by width for
+/// the life of the process. Unlike `PoseidonArity::PARAMETERS` this works for any arity neptune
+/// supports, so trees of a new arity can be prototyped before it gets its own static table.
+pub fn generate_constants<A: 'static + Arity<Fr>>() -> &'static PoseidonConstants<Fr, A> {
+    let mut generated = GENERATED_CONSTANTS
+        .lock()
+        .expect("generated constants lock poisoned");
+    let constants = *generated.entry(A::to_usize() + 1).or_insert_with(|| {
+        Box::leak(Box::new(PoseidonConstants::<Fr, A>::new())) as &'static (dyn Any + Send + Sync)
+    });
+
+    constants
+        .downcast_ref()
+        .expect("constants are keyed by their width")
+}
+
+/// Like `warm_constants`, for each arity used by the Poseidon hasher: the merkle tree arities, the
+/// column arity and `hash_md`'s.
+pub fn warm_all_constants() {
+    warm_constants::<U2>();
+    warm_constants::<U4>();
+    warm_constants::<U8>();
+    warm_constants::<U11>();
+    warm_constants::<U16>();
+    lazy_static::initialize(&POSEIDON_MD_CONSTANTS);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -562,4 +683,80 @@ mod tests {
             circuit_hashed.get_value().expect("get_value failure")
         );
     }
+
+    #[test]
+    fn test_hash2_domain() {
+        let a = PoseidonDomain(u64s_to_u8s([1, 2, 3, 4]));
+        let b = PoseidonDomain(u64s_to_u8s([5, 6, 7, 8]));
+
+        let node = PoseidonFunction::hash2_domain(&a, &b, HashDomain::Node);
+        let commitment = PoseidonFunction::hash2_domain(&a, &b, HashDomain::Commitment);
+
+        assert_eq!(node, PoseidonFunction::hash2(&a, &b));
+        assert_ne!(node, commitment);
+    }
+
+    #[test]
+    fn test_warm_constants() {
+        let leaves: Vec<PoseidonDomain> = (1..=8u64).map(|i| Fr::from(i).into()).collect();
+        let hash = |leaves: &[PoseidonDomain]| {
+            (
+                PoseidonFunction::hash2(&leaves[0], &leaves[1]),
+                PoseidonFunction::hash_md(leaves),
+                PoseidonFunction::default().multi_node(leaves, 0),
+            )
+        };
+
+        let unwarmed = hash(&leaves);
+        warm_all_constants();
+        assert_eq!(hash(&leaves), unwarmed);
+    }
+
+    #[test]
+    fn test_warm_constants_u0() {
+        use generic_array::typenum::U0;
+
+        warm_constants::<U0>();
+    }
+
+    #[test]
+    fn test_ct_eq() {
+        use rand::SeedableRng;
+        use rand_xorshift::XorShiftRng;
+
+        use crate::types::ct_eq;
+
+        let mut rng = XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        for _ in 0..100 {
+            let a = PoseidonDomain::random(&mut rng);
+            let b = PoseidonDomain::random(&mut rng);
+            assert_eq!(bool::from(ct_eq(&a, &b)), a == b);
+            assert!(bool::from(ct_eq(&a, &a)));
+            assert!(bool::from(ct_eq(&a, &PoseidonDomain::from(Fr::from(a)))));
+        }
+    }
+
+    #[test]
+    fn test_generate_constants() {
+        use generic_array::typenum::U3;
+
+        let preimage = [Fr::from(1u64), Fr::from(2u64)];
+        let generated = generate_constants::<U2>();
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, generated).hash(),
+            Poseidon::new_with_preimage(&preimage, &*POSEIDON_CONSTANTS_2).hash()
+        );
+        assert!(std::ptr::eq(generated, generate_constants::<U2>()));
+
+        // An arity without a static table.
+        let preimage = [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
+        let generated = generate_constants::<U3>();
+        assert_eq!(
+            Poseidon::new_with_preimage(&preimage, generated).hash(),
+            Poseidon::new_with_preimage(&preimage, &PoseidonConstants::<Fr, U3>::new()).hash()
+        );
+    }
 }
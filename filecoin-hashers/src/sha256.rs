@@ -317,3 +317,28 @@ impl From<Sha256Domain> for [u8; 32] {
         val.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::HashDomain;
+
+    #[test]
+    fn test_hash2_domain() {
+        let a: Sha256Domain = Fr::from(1u64).into();
+        let b: Sha256Domain = Fr::from(2u64).into();
+        let tag: Sha256Domain = Fr::from(1u64).into();
+
+        let node = Sha256Function::hash2_domain(&a, &b, HashDomain::Node);
+        let commitment = Sha256Function::hash2_domain(&a, &b, HashDomain::Commitment);
+
+        assert_eq!(node, Sha256Function::hash2(&a, &b));
+        assert_ne!(node, commitment);
+        // Folding the tag in with `hash2` would make the commitment a tree node.
+        assert_ne!(
+            commitment,
+            Sha256Function::hash2(&Sha256Function::hash2(&tag, &a), &b)
+        );
+    }
+}
@@ -16,6 +16,7 @@ use merkletree::{
 };
 use rand::RngCore;
 use serde::{de::DeserializeOwned, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 
 pub trait Domain:
     Ord
@@ -44,9 +45,51 @@ pub trait Domain:
     fn random<R: RngCore>(rng: &mut R) -> Self;
 }
 
+/// Compares two domain elements in time independent of where they first differ, for verifiers
+/// checking commitments taken from untrusted proofs.
+pub fn ct_eq<T: Domain>(a: &T, b: &T) -> Choice {
+    a.as_ref().ct_eq(b.as_ref())
+}
+
+/// Separates two-to-one hashes taken for different purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashDomain {
+    /// Internal merkle tree nodes, hashed exactly as `HashFunction::hash2`.
+    Node,
+    /// Commitments derived from other commitments, e.g. `H(comm_c || comm_r_last)`.
+    Commitment,
+}
+
+impl HashDomain {
+    pub(crate) fn tag(self) -> u64 {
+        match self {
+            HashDomain::Node => 0,
+            HashDomain::Commitment => 1,
+        }
+    }
+}
+
 pub trait HashFunction<T: Domain>: Clone + Debug + Send + Sync + LightAlgorithm<T> {
     fn hash(data: &[u8]) -> T;
     fn hash2(a: &T, b: &T) -> T;
+
+    /// Hashes `a` and `b` within `domain`. `HashDomain::Node` is plain `hash2`; every other domain
+    /// hashes the bytes `tag || a || b`, with the tag encoded as a domain element. That preimage is
+    /// longer than any `hash2` preimage, so its outputs cannot collide with tree nodes. Hashers
+    /// whose `hash` does not take arbitrary lengths override this.
+    fn hash2_domain(a: &T, b: &T, domain: HashDomain) -> T {
+        match domain {
+            HashDomain::Node => Self::hash2(a, b),
+            _ => {
+                let tag = T::from(Fr::from(domain.tag()));
+                let mut preimage = Vec::with_capacity(3 * tag.as_ref().len());
+                preimage.extend_from_slice(tag.as_ref());
+                preimage.extend_from_slice(a.as_ref());
+                preimage.extend_from_slice(b.as_ref());
+                Self::hash(&preimage)
+            }
+        }
+    }
     fn hash_md(input: &[T]) -> T {
         // Default to binary.
         assert!(input.len() > 1, "hash_md needs more than one element.");
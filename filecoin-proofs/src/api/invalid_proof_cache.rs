@@ -0,0 +1,161 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// Identifies a `(public inputs, proof)` pair by the sha256 digest of its byte encoding.
+pub type InvalidProofKey = [u8; 32];
+
+/// An opt-in, size-bounded record of recently rejected proofs.
+///
+/// Verifiers which may see the same invalid proof resubmitted can route verification through
+/// `InvalidProofCache::verify`, which rejects a known-bad pair without repeating any
+/// cryptographic work. Once `capacity` rejections are held, the oldest is evicted first.
+#[derive(Debug)]
+pub struct InvalidProofCache {
+    capacity: usize,
+    rejected: Mutex<Rejected>,
+}
+
+#[derive(Debug, Default)]
+struct Rejected {
+    order: VecDeque<InvalidProofKey>,
+    keys: HashSet<InvalidProofKey>,
+}
+
+impl InvalidProofCache {
+    pub fn new(capacity: usize) -> Self {
+        InvalidProofCache {
+            capacity,
+            rejected: Mutex::new(Rejected::default()),
+        }
+    }
+
+    /// Derives the cache key for a `(public inputs, proof)` pair, given as a sequence of byte
+    /// encoded parts. Each part is length-prefixed so that distinct splits never collide.
+    pub fn key<'a, I: IntoIterator<Item = &'a [u8]>>(parts: I) -> InvalidProofKey {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Returns `Ok(false)` immediately if `key` was recently rejected, otherwise runs `verify` and
+    /// records `key` if it reports the proof as invalid. Errors are returned as-is and not cached.
+    pub fn verify<F: FnOnce() -> Result<bool>>(
+        &self,
+        key: InvalidProofKey,
+        verify: F,
+    ) -> Result<bool> {
+        if self.contains(&key) {
+            return Ok(false);
+        }
+
+        let is_valid = verify()?;
+        if !is_valid {
+            self.insert(key);
+        }
+
+        Ok(is_valid)
+    }
+
+    pub fn contains(&self, key: &InvalidProofKey) -> bool {
+        self.rejected
+            .lock()
+            .expect("invalid proof cache poisoned")
+            .keys
+            .contains(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rejected
+            .lock()
+            .expect("invalid proof cache poisoned")
+            .keys
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&self, key: InvalidProofKey) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut rejected = self.rejected.lock().expect("invalid proof cache poisoned");
+        if !rejected.keys.insert(key) {
+            return;
+        }
+        rejected.order.push_back(key);
+
+        if rejected.order.len() > self.capacity {
+            if let Some(oldest) = rejected.order.pop_front() {
+                rejected.keys.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_cached_rejection_skips_verification() {
+        let cache = InvalidProofCache::new(2);
+        let calls = Cell::new(0);
+        let verify = |valid: bool| -> Result<bool> {
+            calls.set(calls.get() + 1);
+            Ok(valid)
+        };
+
+        let bad = InvalidProofCache::key([&b"inputs"[..], &b"bad proof"[..]]);
+        assert!(!cache.verify(bad, || verify(false)).expect("verify failed"));
+        assert_eq!(calls.get(), 1);
+
+        // A known-bad pair is rejected without running verification again.
+        assert!(!cache
+            .verify(bad, || panic!("verification re-run"))
+            .expect("verify failed"));
+        assert_eq!(calls.get(), 1);
+
+        // Valid proofs are never cached.
+        let good = InvalidProofCache::key([&b"inputs"[..], &b"good proof"[..]]);
+        assert!(cache.verify(good, || verify(true)).expect("verify failed"));
+        assert!(cache.verify(good, || verify(true)).expect("verify failed"));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = InvalidProofCache::new(2);
+        let keys: Vec<_> = (0u8..3)
+            .map(|i| InvalidProofCache::key([&[i][..]]))
+            .collect();
+
+        for key in &keys {
+            assert!(!cache.verify(*key, || Ok(false)).expect("verify failed"));
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&keys[0]));
+        assert!(cache.contains(&keys[1]));
+        assert!(cache.contains(&keys[2]));
+    }
+
+    #[test]
+    fn test_key_is_length_prefixed() {
+        assert_ne!(
+            InvalidProofCache::key([&b"ab"[..], &b"c"[..]]),
+            InvalidProofCache::key([&b"a"[..], &b"bc"[..]])
+        );
+    }
+}
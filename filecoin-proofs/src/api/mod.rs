@@ -40,6 +40,7 @@ use crate::{
 };
 
 mod fake_seal;
+mod invalid_proof_cache;
 mod post_util;
 mod seal;
 mod update;
@@ -48,6 +49,7 @@ mod window_post;
 mod winning_post;
 
 pub use fake_seal::*;
+pub use invalid_proof_cache::*;
 pub use post_util::*;
 pub use seal::*;
 pub use update::*;
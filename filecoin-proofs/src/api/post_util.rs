@@ -85,7 +85,7 @@ pub fn generate_fallback_sector_challenges<Tree: 'static + MerkleTreeTrait>(
     let num_sectors_per_chunk = post_config.sector_count;
     let partitions = match post_config.typ {
         PoStType::Window => {
-            get_partitions_for_window_post(pub_sectors.len(), post_config).unwrap_or(1)
+            get_partitions_for_window_post(pub_sectors.len(), post_config)?.unwrap_or(1)
         }
         PoStType::Winning => 1,
     };
@@ -246,16 +246,33 @@ pub fn partition_vanilla_proofs<Tree: MerkleTreeTrait>(
     Ok(partition_proofs)
 }
 
+/// Returns the number of window PoSt partitions, and so the number of `k` values, needed to prove
+/// `total_sector_count` sectors. Each partition proves `post_config.sector_count` sectors and the
+/// last one is padded, so any remainder takes a partition of its own. Fails if there are no
+/// sectors to prove or `post_config` allows none per partition.
+pub fn window_post_partition_count(
+    total_sector_count: usize,
+    post_config: &PoStConfig,
+) -> Result<usize> {
+    ensure!(
+        post_config.sector_count > 0,
+        "post config must allow at least one sector per partition"
+    );
+    ensure!(total_sector_count > 0, "no sectors to prove");
+
+    Ok((total_sector_count - 1) / post_config.sector_count + 1)
+}
+
 pub(crate) fn get_partitions_for_window_post(
     total_sector_count: usize,
     post_config: &PoStConfig,
-) -> Option<usize> {
-    let partitions = (total_sector_count as f32 / post_config.sector_count as f32).ceil() as usize;
+) -> Result<Option<usize>> {
+    let partitions = window_post_partition_count(total_sector_count, post_config)?;
 
     if partitions > 1 {
-        Some(partitions)
+        Ok(Some(partitions))
     } else {
-        None
+        Ok(None)
     }
 }
 
@@ -395,3 +412,51 @@ pub fn get_num_partition_for_fallback_post(config: &PoStConfig, num_sectors: usi
         PoStType::Winning => 1,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use storage_proofs_core::api_version::ApiVersion;
+
+    use crate::{constants::SECTOR_SIZE_32_GIB, types::SectorSize};
+
+    fn window_post_config(sector_count: usize) -> PoStConfig {
+        PoStConfig {
+            sector_size: SectorSize(SECTOR_SIZE_32_GIB),
+            challenge_count: 10,
+            sector_count,
+            typ: PoStType::Window,
+            priority: false,
+            api_version: ApiVersion::V1_1_0,
+        }
+    }
+
+    #[test]
+    fn test_window_post_partition_count() {
+        let post_config = window_post_config(2349);
+
+        let count = |total| {
+            window_post_partition_count(total, &post_config).expect("partition count failure")
+        };
+        assert_eq!(count(2349), 1);
+        assert_eq!(count(3 * 2349), 3);
+
+        assert_eq!(count(1), 1);
+        assert_eq!(count(2350), 2);
+        assert_eq!(count(3 * 2349 - 1), 3);
+        assert_eq!(count(usize::MAX), (usize::MAX - 1) / 2349 + 1);
+
+        assert!(window_post_partition_count(0, &post_config).is_err());
+        assert!(window_post_partition_count(1, &window_post_config(0)).is_err());
+
+        assert_eq!(
+            get_partitions_for_window_post(2349, &post_config).expect("partitions failure"),
+            None
+        );
+        assert_eq!(
+            get_partitions_for_window_post(2350, &post_config).expect("partitions failure"),
+            Some(2)
+        );
+    }
+}
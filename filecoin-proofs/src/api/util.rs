@@ -2,8 +2,9 @@ use std::mem::size_of;
 
 use anyhow::{Context, Result};
 use blstrs::Scalar as Fr;
+use ff::PrimeField;
 use filecoin_hashers::{Domain, Hasher};
-use fr32::{bytes_into_fr, fr_into_bytes};
+use fr32::{bytes_into_fr, bytes_into_fr_repr_safe, fr_into_bytes};
 use merkletree::merkle::{get_merkle_tree_leafs, get_merkle_tree_len};
 use storage_proofs_core::merkle::{get_base_tree_count, MerkleTreeTrait};
 use typenum::Unsigned;
@@ -19,6 +20,15 @@ pub fn as_safe_commitment<H: Domain, T: AsRef<str>>(
         .with_context(|| format!("Invalid commitment ({})", commitment_name.as_ref(),))
 }
 
+/// Maps a 32-byte randomness beacon entry into the field by clearing its two most significant
+/// bits, as the network does before passing PoSt randomness to the proofs. Every value below
+/// 2^254 is a canonical field element, so the result's bytes are always accepted as a
+/// `ChallengeSeed`.
+pub fn randomness_from_beacon(entry: &[u8; 32]) -> Fr {
+    Fr::from_repr_vartime(bytes_into_fr_repr_safe(entry))
+        .expect("values below 2^254 are field elements")
+}
+
 pub fn commitment_from_fr(fr: Fr) -> Commitment {
     let mut commitment = [0; 32];
     for (i, b) in fr_into_bytes(&fr).iter().enumerate() {
@@ -38,3 +48,32 @@ pub fn get_base_tree_size<Tree: MerkleTreeTrait>(sector_size: SectorSize) -> Res
 pub fn get_base_tree_leafs<Tree: MerkleTreeTrait>(base_tree_size: usize) -> Result<usize> {
     get_merkle_tree_leafs(base_tree_size, Tree::Arity::to_usize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonDomain;
+
+    #[test]
+    fn test_randomness_from_beacon() {
+        let mut entry = [0u8; 32];
+        for (i, byte) in entry.iter_mut().enumerate() {
+            *byte = 0xe0 | i as u8;
+        }
+
+        let fr = randomness_from_beacon(&entry);
+
+        let mut expected = entry;
+        expected[31] = 0x3f;
+        assert_eq!(fr.to_repr(), expected);
+
+        // Beacon entries are otherwise rejected if they are not already field elements.
+        assert!(as_safe_commitment::<PoseidonDomain, _>(&entry, "randomness").is_err());
+        let seed = commitment_from_fr(fr);
+        assert_eq!(
+            as_safe_commitment::<PoseidonDomain, _>(&seed, "randomness").expect("invalid seed"),
+            PoseidonDomain::from(fr)
+        );
+    }
+}
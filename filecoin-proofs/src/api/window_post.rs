@@ -1,11 +1,13 @@
 use std::collections::BTreeMap;
 
 use anyhow::{ensure, Context, Result};
-use filecoin_hashers::Hasher;
+use filecoin_hashers::{Domain, Hasher};
+use generic_array::typenum::Unsigned;
 use log::info;
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
+    measurements::{measure_op, observe, Observer, Operation},
     merkle::MerkleTreeTrait,
     multi_proof::MultiProof,
     sector::SectorId,
@@ -17,7 +19,7 @@ use storage_proofs_post::fallback::{
 use crate::{
     api::{
         as_safe_commitment, get_partitions_for_window_post, partition_vanilla_proofs,
-        single_partition_vanilla_proofs,
+        single_partition_vanilla_proofs, InvalidProofCache,
     },
     caches::{get_post_params, get_post_verifying_key},
     parameters::window_post_setup_params,
@@ -47,7 +49,7 @@ pub fn generate_window_post_with_vanilla<Tree: 'static + MerkleTreeTrait>(
         as_safe_commitment(&prover_id, "prover_id")?;
 
     let vanilla_params = window_post_setup_params(post_config);
-    let partitions = get_partitions_for_window_post(vanilla_proofs.len(), post_config);
+    let partitions = get_partitions_for_window_post(vanilla_proofs.len(), post_config)?;
 
     let setup_params = compound_proof::SetupParams {
         vanilla_params,
@@ -113,7 +115,7 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
     let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
 
     let vanilla_params = window_post_setup_params(post_config);
-    let partitions = get_partitions_for_window_post(replicas.len(), post_config);
+    let partitions = get_partitions_for_window_post(replicas.len(), post_config)?;
 
     let sector_count = vanilla_params.sector_count;
     let setup_params = compound_proof::SetupParams {
@@ -126,16 +128,18 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
         FallbackPoStCompound::setup(&setup_params)?;
     let groth_params = get_post_params::<Tree>(post_config)?;
 
-    let trees: Vec<_> = replicas
-        .par_iter()
-        .map(|(sector_id, replica)| {
-            replica
-                .merkle_tree(post_config.sector_size)
-                .with_context(|| {
-                    format!("generate_window_post: merkle_tree failed: {:?}", sector_id)
-                })
-        })
-        .collect::<Result<_>>()?;
+    let trees: Vec<_> = measure_op(Operation::PostGenerateTrees, || {
+        replicas
+            .par_iter()
+            .map(|(sector_id, replica)| {
+                replica
+                    .merkle_tree(post_config.sector_size)
+                    .with_context(|| {
+                        format!("generate_window_post: merkle_tree failed: {:?}", sector_id)
+                    })
+            })
+            .collect::<Result<_>>()
+    })?;
 
     let mut pub_sectors = Vec::with_capacity(sector_count);
     let mut priv_sectors = Vec::with_capacity(sector_count);
@@ -176,6 +180,21 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
     proof.to_vec()
 }
 
+/// Generates a Window proof-of-spacetime like `generate_window_post`, reporting the wall time
+/// of each proving phase (tree generation, vanilla proofs, circuit proofs) to `observer`. Phases
+/// are only timed with the `measurements` feature; without it `observer` is never called.
+pub fn generate_window_post_with_observer<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+    observer: Observer,
+) -> Result<SnarkProof> {
+    observe(Some(observer), || {
+        generate_window_post::<Tree>(post_config, randomness, replicas, prover_id)
+    })
+}
+
 /// Verifies a window proof-of-spacetime.
 pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
@@ -195,7 +214,7 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
     let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
 
     let vanilla_params = window_post_setup_params(post_config);
-    let partitions = get_partitions_for_window_post(replicas.len(), post_config);
+    let partitions = get_partitions_for_window_post(replicas.len(), post_config)?;
 
     let setup_params = compound_proof::SetupParams {
         vanilla_params,
@@ -247,6 +266,62 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
     Ok(true)
 }
 
+/// Verifies a window proof-of-spacetime, consulting `cache` first so that a recently rejected
+/// proof for the same public inputs is rejected again without re-running verification.
+///
+/// The cache key covers every `post_config` field which affects verification and the shape of
+/// `Tree`, so a proof rejected under one configuration is still verified under another.
+pub fn verify_window_post_cached<Tree: 'static + MerkleTreeTrait>(
+    cache: &InvalidProofCache,
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    let mut config = Vec::with_capacity(3 * 8 + 1);
+    config.extend_from_slice(&u64::from(post_config.sector_size).to_le_bytes());
+    config.extend_from_slice(&(post_config.challenge_count as u64).to_le_bytes());
+    config.extend_from_slice(&(post_config.sector_count as u64).to_le_bytes());
+    config.push(match post_config.typ {
+        PoStType::Winning => 0,
+        PoStType::Window => 1,
+    });
+    let api_version = post_config.api_version.to_string();
+
+    let tree_shape = [
+        Tree::Arity::to_u64(),
+        Tree::SubTreeArity::to_u64(),
+        Tree::TopTreeArity::to_u64(),
+    ]
+    .iter()
+    .flat_map(|arity| arity.to_le_bytes())
+    .collect::<Vec<u8>>();
+    let hasher = Tree::Hasher::name();
+
+    let mut sectors = Vec::with_capacity(replicas.len() * 40);
+    for (sector_id, replica) in replicas {
+        let comm_r: <Tree::Hasher as Hasher>::Domain = replica.safe_comm_r()?;
+        sectors.extend_from_slice(&u64::from(*sector_id).to_le_bytes());
+        sectors.extend_from_slice(&comm_r.into_bytes());
+    }
+
+    let key = InvalidProofCache::key([
+        &config[..],
+        api_version.as_bytes(),
+        &tree_shape[..],
+        hasher.as_bytes(),
+        &randomness[..],
+        &prover_id[..],
+        &sectors[..],
+        proof,
+    ]);
+
+    cache.verify(key, || {
+        verify_window_post::<Tree>(post_config, randomness, replicas, prover_id, proof)
+    })
+}
+
 /// Generates a Window proof-of-spacetime with provided vanilla proofs of a single partition.
 pub fn generate_single_window_post_with_vanilla<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
@@ -267,7 +342,7 @@ pub fn generate_single_window_post_with_vanilla<Tree: 'static + MerkleTreeTrait>
         as_safe_commitment(&prover_id, "prover_id")?;
 
     let vanilla_params = window_post_setup_params(post_config);
-    let partitions = get_partitions_for_window_post(vanilla_proofs.len(), post_config);
+    let partitions = get_partitions_for_window_post(vanilla_proofs.len(), post_config)?;
 
     let setup_params = compound_proof::SetupParams {
         vanilla_params,
@@ -3,6 +3,7 @@ use filecoin_hashers::Hasher;
 use log::info;
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
+    measurements::{measure_op, observe, Observer, Operation},
     merkle::MerkleTreeTrait,
     multi_proof::MultiProof,
     sector::SectorId,
@@ -129,16 +130,18 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
         FallbackPoStCompound::setup(&setup_params)?;
     let groth_params = get_post_params::<Tree>(post_config)?;
 
-    let trees = replicas
-        .iter()
-        .map(|(sector_id, replica)| {
-            replica
-                .merkle_tree(post_config.sector_size)
-                .with_context(|| {
-                    format!("generate_winning_post: merkle_tree failed: {:?}", sector_id)
-                })
-        })
-        .collect::<Result<Vec<_>>>()?;
+    let trees = measure_op(Operation::PostGenerateTrees, || {
+        replicas
+            .iter()
+            .map(|(sector_id, replica)| {
+                replica
+                    .merkle_tree(post_config.sector_size)
+                    .with_context(|| {
+                        format!("generate_winning_post: merkle_tree failed: {:?}", sector_id)
+                    })
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
 
     let mut pub_sectors = Vec::with_capacity(param_sector_count);
     let mut priv_sectors = Vec::with_capacity(param_sector_count);
@@ -183,8 +186,26 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     Ok(proof)
 }
 
+/// Generates a Winning proof-of-spacetime like `generate_winning_post`, reporting the wall time
+/// of each proving phase (tree generation, vanilla proofs, circuit proofs) to `observer`. Phases
+/// are only timed with the `measurements` feature; without it `observer` is never called.
+pub fn generate_winning_post_with_observer<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PrivateReplicaInfo<Tree>)],
+    prover_id: ProverId,
+    observer: Observer,
+) -> Result<SnarkProof> {
+    observe(Some(observer), || {
+        generate_winning_post::<Tree>(post_config, randomness, replicas, prover_id)
+    })
+}
+
 /// Given some randomness and the length of available sectors, generates the challenged sector.
 ///
+/// `randomness` must be a field element; a raw randomness beacon entry is mapped into the field
+/// with `randomness_from_beacon`.
+///
 /// The returned values are indices in the range of `0..sector_set_size`, requiring the caller
 /// to match the index to the correct sector.
 pub fn generate_winning_post_sector_challenge<Tree: MerkleTreeTrait>(
@@ -22,14 +22,15 @@ use filecoin_proofs::{
     seal_pre_commit_phase2, unseal_range, validate_cache_for_commit,
     validate_cache_for_precommit_phase2, verify_aggregate_seal_commit_proofs,
     verify_empty_sector_update_proof, verify_partition_proofs, verify_seal,
-    verify_single_partition_proof, verify_window_post, verify_winning_post, Commitment,
-    DefaultTreeDomain, MerkleTreeTrait, PaddedBytesAmount, PieceInfo, PoRepConfig,
-    PoRepProofPartitions, PoStConfig, PoStType, PrivateReplicaInfo, ProverId, PublicReplicaInfo,
-    SealCommitOutput, SealPreCommitOutput, SealPreCommitPhase1Output, SectorShape16KiB,
-    SectorShape2KiB, SectorShape32KiB, SectorShape4KiB, SectorSize, SectorUpdateConfig,
-    UnpaddedByteIndex, UnpaddedBytesAmount, POREP_PARTITIONS, SECTOR_SIZE_16_KIB,
-    SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB, WINDOW_POST_CHALLENGE_COUNT,
-    WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
+    verify_single_partition_proof, verify_window_post, verify_window_post_cached,
+    verify_winning_post, Commitment, DefaultTreeDomain, InvalidProofCache, MerkleTreeTrait,
+    PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
+    PrivateReplicaInfo, ProverId, PublicReplicaInfo, SealCommitOutput, SealPreCommitOutput,
+    SealPreCommitPhase1Output, SectorShape16KiB, SectorShape2KiB, SectorShape32KiB,
+    SectorShape4KiB, SectorSize, SectorUpdateConfig, UnpaddedByteIndex, UnpaddedBytesAmount,
+    POREP_PARTITIONS, SECTOR_SIZE_16_KIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB,
+    WINDOW_POST_CHALLENGE_COUNT, WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT,
+    WINNING_POST_SECTOR_COUNT,
 };
 use fr32::bytes_into_fr;
 use log::info;
@@ -1361,6 +1362,91 @@ fn window_post<Tree: 'static + MerkleTreeTrait>(
     Ok(())
 }
 
+#[test]
+#[ignore]
+fn test_verify_window_post_cached_api_version() -> Result<()> {
+    type Tree = SectorShape2KiB;
+
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let sector_count = *WINDOW_POST_SECTOR_COUNT
+        .read()
+        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
+        .get(&sector_size)
+        .expect("unknown sector size");
+
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let prover_fr: <<Tree as MerkleTreeTrait>::Hasher as Hasher>::Domain =
+        Fr::random(&mut rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+
+    let mut sectors = Vec::with_capacity(sector_count);
+    let mut pub_replicas = BTreeMap::new();
+    let mut priv_replicas = BTreeMap::new();
+    for _ in 0..sector_count {
+        let (sector_id, replica, comm_r, cache_dir) = create_fake_seal::<_, Tree>(
+            &mut rng,
+            sector_size,
+            &ARBITRARY_POREP_ID_V1_1_0,
+            ApiVersion::V1_1_0,
+        )?;
+        priv_replicas.insert(
+            sector_id,
+            PrivateReplicaInfo::new(replica.path().into(), comm_r, cache_dir.path().into())?,
+        );
+        pub_replicas.insert(sector_id, PublicReplicaInfo::new(comm_r)?);
+        sectors.push((replica, cache_dir));
+    }
+
+    let random_fr: <<Tree as MerkleTreeTrait>::Hasher as Hasher>::Domain =
+        Fr::random(&mut rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = |api_version| PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    let proof = generate_window_post::<Tree>(
+        &config(ApiVersion::V1_1_0),
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+
+    // The challenges derived under V1_0_0 differ, so the proof is rejected and cached.
+    let cache = InvalidProofCache::new(8);
+    let valid = verify_window_post_cached::<Tree>(
+        &cache,
+        &config(ApiVersion::V1_0_0),
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &proof,
+    )?;
+    assert!(!valid, "proof verified under the wrong api version");
+    assert_eq!(cache.len(), 1);
+
+    // The same bytes under the config they were generated for are not answered from the cache.
+    let valid = verify_window_post_cached::<Tree>(
+        &cache,
+        &config(ApiVersion::V1_1_0),
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &proof,
+    )?;
+    assert!(valid, "proof did not verify");
+    assert_eq!(cache.len(), 1);
+
+    Ok(())
+}
+
 fn generate_piece_file(sector_size: u64) -> Result<(NamedTempFile, Vec<u8>)> {
     let number_of_bytes_in_piece = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
 
@@ -0,0 +1,39 @@
+#![no_main]
+
+use std::convert::TryInto;
+
+use filecoin_hashers::poseidon::PoseidonDomain;
+use fr32::fr_from_bytes_lossy;
+use libfuzzer_sys::fuzz_target;
+use storage_proofs_core::{leaf_source::LeafSource, util::NODE_SIZE};
+
+// Extracts the challenged leaves of an arbitrary replica the way `validate_replica_leaves` does:
+// `LeafSource::read_fr` on each challenge, then conversion into the tree's domain. No replica or
+// challenge may panic, a challenge past the end must be rejected, and every leaf which decodes
+// must convert back to the bytes it was read from.
+fuzz_target!(|input: (Vec<u64>, Vec<u8>)| {
+    let (challenges, replica) = input;
+    let replica: &[u8] = &replica;
+    let nodes = replica.len() / NODE_SIZE;
+
+    for challenge in challenges {
+        let node = challenge as usize;
+        let bytes = match replica.read_node(node) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                assert!(node >= nodes, "node {} of {} rejected", node, nodes);
+                continue;
+            }
+        };
+        assert!(node < nodes, "node {} of {} accepted", node, nodes);
+
+        let leaf: &[u8; NODE_SIZE] = bytes[..].try_into().expect("nodes are NODE_SIZE bytes");
+        match replica.read_fr(node) {
+            Ok(fr) => {
+                let domain = PoseidonDomain::from(fr);
+                assert_eq!(AsRef::<[u8]>::as_ref(&domain), &leaf[..]);
+            }
+            Err(_) => assert!(fr_from_bytes_lossy(leaf).is_none()),
+        }
+    }
+});
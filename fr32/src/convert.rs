@@ -6,6 +6,10 @@ use ff::PrimeField;
 pub enum Error {
     #[error("Bytes could not be converted to Fr")]
     BadFrBytes,
+    #[error("{0} bytes cannot hold an Fr, expected 32")]
+    WrongLength(usize),
+    #[error("bytes are not a canonical Fr (top two bits set: {top_bits_set})")]
+    NonCanonical { top_bits_set: bool },
 }
 
 /// Contains one or more 32-byte chunks whose little-endian values represent Frs.
@@ -20,13 +24,68 @@ pub type Fr32Vec = Vec<u8>;
 /// - Value MUST represent a valid Fr.
 pub type Fr32Ary = [u8; 32];
 
+/// Maps a leaf between the bytes stored in a replica and the little-endian `Fr` representation the
+/// tree committed to. Replicas with non-standard leaf encodings (e.g. a fixed offset or domain
+/// tag) supply their own; the standard encoding is `IdentityLeafTransform`.
+pub trait LeafTransform {
+    /// Turns stored leaf bytes into an `Fr` representation, before `from_repr` is applied.
+    fn decode(&self, bytes: &mut Fr32Ary);
+    /// Inverse of `decode`: turns an `Fr` representation into stored leaf bytes.
+    fn encode(&self, repr: &mut Fr32Ary);
+}
+
+/// Leaves are stored as their `Fr` representation.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdentityLeafTransform;
+
+impl LeafTransform for IdentityLeafTransform {
+    #[inline]
+    fn decode(&self, _bytes: &mut Fr32Ary) {}
+
+    #[inline]
+    fn encode(&self, _repr: &mut Fr32Ary) {}
+}
+
 /// Takes a slice of bytes (little-endian, non-Montgomery form) and returns an Fr if byte slice is
 /// exactly 32 bytes and does not overflow. Otherwise, returns a BadFrBytesError.
 pub fn bytes_into_fr(le_bytes: &[u8]) -> Result<Fr> {
-    ensure!(le_bytes.len() == 32, Error::BadFrBytes);
+    bytes_into_fr_with(le_bytes, &IdentityLeafTransform)
+}
+
+/// Like `bytes_into_fr`, but first decodes the stored leaf bytes with `transform`.
+pub fn bytes_into_fr_with<T: LeafTransform + ?Sized>(le_bytes: &[u8], transform: &T) -> Result<Fr> {
+    try_fr_from_bytes_with(le_bytes, transform).map_err(|_| Error::BadFrBytes.into())
+}
+
+/// Like `bytes_into_fr`, but says why the bytes were rejected instead of returning `BadFrBytes`:
+/// `WrongLength` if the slice is not 32 bytes long, otherwise `NonCanonical`, which records
+/// whether the two most significant bits were set or the value merely reached the modulus. Useful
+/// for telling a corrupt replica node from a misaligned read.
+pub fn try_fr_from_bytes(le_bytes: &[u8]) -> std::result::Result<Fr, Error> {
+    try_fr_from_bytes_with(le_bytes, &IdentityLeafTransform)
+}
+
+/// Like `try_fr_from_bytes`, but first decodes the stored leaf bytes with `transform`.
+pub fn try_fr_from_bytes_with<T: LeafTransform + ?Sized>(
+    le_bytes: &[u8],
+    transform: &T,
+) -> std::result::Result<Fr, Error> {
+    if le_bytes.len() != 32 {
+        return Err(Error::WrongLength(le_bytes.len()));
+    }
     let mut repr = [0u8; 32];
     repr.copy_from_slice(le_bytes);
-    Fr::from_repr_vartime(repr).ok_or_else(|| Error::BadFrBytes.into())
+    transform.decode(&mut repr);
+    Fr::from_repr_vartime(repr).ok_or(Error::NonCanonical {
+        top_bits_set: repr[31] & 0b1100_0000 != 0,
+    })
+}
+
+/// Decodes 32 little-endian bytes into an `Fr`, returning `None` unless they are canonical. Unlike
+/// `bytes_into_fr` this never panics or allocates an error, whatever the input, so it can be fed
+/// arbitrary replica bytes, e.g. by a fuzzer.
+pub fn fr_from_bytes_lossy(le_bytes: &[u8; 32]) -> Option<Fr> {
+    Fr::from_repr_vartime(*le_bytes)
 }
 
 /// Converts a slice of 32 bytes (little-endian, non-Montgomery form) into an `Fr::Repr` by
@@ -46,6 +105,14 @@ pub fn fr_into_bytes(fr: &Fr) -> Fr32Vec {
     fr.to_repr().to_vec()
 }
 
+/// Like `fr_into_bytes`, but encodes the result into stored leaf bytes with `transform`.
+#[inline]
+pub fn fr_into_bytes_with<T: LeafTransform + ?Sized>(fr: &Fr, transform: &T) -> Fr32Vec {
+    let mut repr = fr.to_repr();
+    transform.encode(&mut repr);
+    repr.to_vec()
+}
+
 /// Takes an Fr and copies exactly 32 bytes guaranteed to contain a valid Fr into the fr_bytes output slice.
 #[inline]
 pub fn fr_into_bytes_slice(fr: &Fr, fr_bytes: &mut [u8]) {
@@ -57,10 +124,52 @@ pub fn u64_into_fr(n: u64) -> Fr {
     Fr::from(n)
 }
 
+/// Returns the 256 bits of `fr`'s canonical value, least significant first. This is the order in
+/// which `multipack::compute_multipacking` packs bits into an `Fr`, and matches the little-endian
+/// byte order of `fr_into_bytes` with each byte's bits taken low to high.
+pub fn fr_to_le_bits(fr: &Fr) -> Vec<bool> {
+    fr.to_repr()
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .collect()
+}
+
+/// Returns the 256 bits of `fr`'s canonical value, most significant first: `fr_to_le_bits`
+/// reversed.
+pub fn fr_to_be_bits(fr: &Fr) -> Vec<bool> {
+    let mut bits = fr_to_le_bits(fr);
+    bits.reverse();
+    bits
+}
+
+/// Inverse of `fr_to_le_bits`. Fails unless given 256 bits encoding a canonical `Fr`.
+pub fn fr_from_le_bits(bits: &[bool]) -> Result<Fr> {
+    ensure!(bits.len() == 256, "expected 256 bits, got {}", bits.len());
+    let mut repr = [0u8; 32];
+    for (byte, chunk) in repr.iter_mut().zip(bits.chunks(8)) {
+        *byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |byte, (i, &bit)| byte | ((bit as u8) << i));
+    }
+    try_fr_from_bytes(&repr).map_err(Into::into)
+}
+
+/// Inverse of `fr_to_be_bits`. Fails unless given 256 bits encoding a canonical `Fr`.
+pub fn fr_from_be_bits(bits: &[bool]) -> Result<Fr> {
+    let le_bits: Vec<bool> = bits.iter().rev().copied().collect();
+    fr_from_le_bits(&le_bits)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use ff::Field;
+    use proptest::{prelude::any, prop_compose, proptest};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
     fn bytes_fr_test(bytes: Fr32Ary, expect_success: bool) {
         let b = &bytes[..];
         let fr_result = bytes_into_fr(b);
@@ -115,4 +224,129 @@ mod tests {
             false,
         );
     }
+
+    #[test]
+    fn test_try_fr_from_bytes_errors() {
+        // The BLS12-381 scalar field modulus, little-endian.
+        let mut bytes: Fr32Ary = [
+            0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4,
+            0xbd, 0x53, 0x05, 0xd8, 0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29,
+            0x53, 0xa7, 0xed, 0x73,
+        ];
+
+        bytes[0] = 0x00;
+        assert_eq!(
+            try_fr_from_bytes(&bytes).expect("modulus - 1 rejected"),
+            -Fr::one()
+        );
+
+        bytes[0] = 0x02;
+        assert!(matches!(
+            try_fr_from_bytes(&bytes),
+            Err(Error::NonCanonical {
+                top_bits_set: false
+            })
+        ));
+
+        assert!(matches!(
+            try_fr_from_bytes(&[0xff; 32]),
+            Err(Error::NonCanonical { top_bits_set: true })
+        ));
+
+        assert!(matches!(
+            try_fr_from_bytes(&bytes[..31]),
+            Err(Error::WrongLength(31))
+        ));
+
+        // The leaf extraction path keeps reporting `BadFrBytes`.
+        let err = bytes_into_fr(&bytes).expect_err("modulus + 1 accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::BadFrBytes)
+        ));
+    }
+
+    prop_compose! {
+        fn arb_fr()(seed in any::<[u8; 16]>()) -> Fr {
+            Fr::random(XorShiftRng::from_seed(seed))
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn fr_from_bytes_lossy_any_bytes(bytes in any::<[u8; 32]>()) {
+            let lossy = fr_from_bytes_lossy(&bytes);
+            assert_eq!(lossy, try_fr_from_bytes(&bytes).ok());
+            if let Some(fr) = lossy {
+                assert_eq!(fr_into_bytes(&fr), bytes.to_vec());
+            }
+        }
+
+        #[test]
+        fn fr_le_bits_roundtrip(fr in arb_fr()) {
+            let bits = fr_to_le_bits(&fr);
+            assert_eq!(bits.len(), 256);
+            assert_eq!(fr_from_le_bits(&bits).expect("from le bits failure"), fr);
+        }
+
+        #[test]
+        fn fr_be_bits_roundtrip(fr in arb_fr()) {
+            let bits = fr_to_be_bits(&fr);
+            assert_eq!(bits.len(), 256);
+            assert_eq!(fr_from_be_bits(&bits).expect("from be bits failure"), fr);
+        }
+    }
+
+    #[test]
+    fn test_fr_bit_order() {
+        // 2^0 + 2^9: bit 0 of byte 0 and bit 1 of byte 1.
+        let fr = Fr::from(0x201u64);
+
+        let le_bits = fr_to_le_bits(&fr);
+        let set: Vec<usize> = (0..256).filter(|&i| le_bits[i]).collect();
+        assert_eq!(set, vec![0, 9]);
+
+        let be_bits = fr_to_be_bits(&fr);
+        let set: Vec<usize> = (0..256).filter(|&i| be_bits[i]).collect();
+        assert_eq!(set, vec![246, 255]);
+
+        // Reading bits in the other order gives a different element, or none at all.
+        assert!(fr_from_be_bits(&le_bits).map_or(true, |other| other != fr));
+        assert!(fr_from_le_bits(&le_bits[..255]).is_err());
+    }
+
+    /// Stores leaves with their low 31 bytes masked by a fixed tag.
+    struct MaskedLeafTransform(u8);
+
+    impl LeafTransform for MaskedLeafTransform {
+        fn decode(&self, bytes: &mut Fr32Ary) {
+            bytes[..31].iter_mut().for_each(|b| *b ^= self.0);
+        }
+
+        fn encode(&self, repr: &mut Fr32Ary) {
+            self.decode(repr);
+        }
+    }
+
+    #[test]
+    fn test_leaf_transform_round_trip() {
+        let transform = MaskedLeafTransform(0xa5);
+        let fr = Fr::from(0x0123_4567_89ab_cdefu64);
+
+        let stored = fr_into_bytes_with(&fr, &transform);
+        assert_ne!(stored, fr_into_bytes(&fr));
+
+        let decoded = bytes_into_fr_with(&stored, &transform).expect("decode failure");
+        assert_eq!(decoded, fr);
+        assert_ne!(
+            bytes_into_fr(&stored).expect("stored bytes are a valid Fr"),
+            fr
+        );
+
+        assert_eq!(
+            bytes_into_fr_with(&fr_into_bytes(&fr), &IdentityLeafTransform)
+                .expect("decode failure"),
+            fr
+        );
+    }
 }
@@ -65,5 +65,37 @@ fn merkle_benchmark_poseidon(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, merkle_benchmark_sha256, merkle_benchmark_poseidon);
+fn merkle_benchmark_cache_levels(c: &mut Criterion) {
+    // A 32 KiB sector.
+    let n_nodes = 1024;
+    let mut rng = thread_rng();
+    let mut data: Vec<u8> = Vec::with_capacity(32 * n_nodes);
+    for _ in 0..n_nodes {
+        data.extend(PoseidonDomain::random(&mut rng).into_bytes());
+    }
+    let tree =
+        create_base_merkle_tree::<BinaryMerkleTree<PoseidonHasher>>(None, n_nodes, &data).unwrap();
+    let challenges: Vec<usize> = (0..64).map(|_| rng.gen_range(0..n_nodes)).collect();
+
+    let mut group = c.benchmark_group("merkletree-cache-levels");
+    for &cache_levels in &[0, 2, 4, 8, 11] {
+        let cached = tree.with_cache_levels(cache_levels).unwrap();
+        group.bench_function(format!("poseidon-{}-{}", n_nodes, cache_levels), |b| {
+            b.iter(|| {
+                for &challenge in &challenges {
+                    black_box(cached.gen_proof(challenge).unwrap());
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    merkle_benchmark_sha256,
+    merkle_benchmark_poseidon,
+    merkle_benchmark_cache_levels
+);
 criterion_main!(benches);
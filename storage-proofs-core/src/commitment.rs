@@ -0,0 +1,178 @@
+use anyhow::ensure;
+use filecoin_hashers::Hasher;
+use generic_array::typenum::Unsigned;
+use merkletree::hash::Algorithm;
+
+use crate::{
+    error::{Error, Result},
+    merkle::{get_base_tree_count, MerkleTreeTrait},
+};
+
+/// Derives `comm_c` from a replica's column hashes.
+///
+/// Each column hash is the Poseidon hash of a single node's labels across all SDR layers, and
+/// `comm_c` is the root of `tree_c` built over those hashes with the shape of `Tree`. `roots` must
+/// hold exactly one column hash per sector node, in node order.
+pub fn comm_c_from_column_roots<Tree: MerkleTreeTrait>(
+    roots: &[<Tree::Hasher as Hasher>::Domain],
+) -> Result<<Tree::Hasher as Hasher>::Domain> {
+    let base_arity = Tree::Arity::to_usize();
+    let base_tree_count = get_base_tree_count::<Tree>();
+    let base_tree_leafs = roots.len() / base_tree_count;
+
+    ensure!(
+        base_tree_leafs >= base_arity && base_tree_leafs * base_tree_count == roots.len(),
+        Error::InvalidMerkleTreeArgs(roots.len(), base_tree_count, base_tree_leafs)
+    );
+
+    let mut height = 0;
+    let mut nodes = roots.to_vec();
+    while nodes.len() > base_tree_count {
+        ensure!(
+            nodes.len() % (base_arity * base_tree_count) == 0,
+            Error::InvalidMerkleTreeArgs(roots.len(), base_tree_count, base_tree_leafs)
+        );
+        nodes = hash_layer::<Tree::Hasher>(&nodes, base_arity, height);
+        height += 1;
+    }
+
+    for arity in [
+        Tree::SubTreeArity::to_usize(),
+        Tree::TopTreeArity::to_usize(),
+    ] {
+        if arity > 0 {
+            nodes = hash_layer::<Tree::Hasher>(&nodes, arity, height);
+            height += 1;
+        }
+    }
+
+    Ok(nodes[0])
+}
+
+fn hash_layer<H: Hasher>(nodes: &[H::Domain], arity: usize, height: usize) -> Vec<H::Domain> {
+    let mut a = H::Function::default();
+    nodes
+        .chunks(arity)
+        .map(|children| {
+            a.reset();
+            a.multi_node(children, height)
+        })
+        .collect()
+}
+
+/// A `tree_c` shaped fixture shared by the tests of every `comm_c` derivation.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use filecoin_hashers::{poseidon::PoseidonHasher, Domain, Hasher};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::{
+        merkle::{
+            generate_tree, get_base_tree_count, DiskStore, MerkleTreeTrait, MerkleTreeWrapper,
+        },
+        TEST_SEED,
+    };
+
+    pub(crate) type ColumnTree<U, V, W> =
+        MerkleTreeWrapper<PoseidonHasher, DiskStore<<PoseidonHasher as Hasher>::Domain>, U, V, W>;
+
+    /// Builds a `Tree` over 64 random column hashes per base tree, returning the column hashes in
+    /// node order together with the tree's root, the `comm_c` they should derive.
+    pub(crate) fn column_roots<Tree: 'static + MerkleTreeTrait>() -> (
+        Vec<<Tree::Hasher as Hasher>::Domain>,
+        <Tree::Hasher as Hasher>::Domain,
+    ) {
+        let nodes = 64 * get_base_tree_count::<Tree>();
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (data, tree) = generate_tree::<Tree, _>(&mut rng, nodes, None);
+
+        let roots = data
+            .chunks(32)
+            .map(|bytes| {
+                <Tree::Hasher as Hasher>::Domain::try_from_bytes(bytes)
+                    .expect("try from bytes failure")
+            })
+            .collect();
+        (roots, tree.root())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blstrs::Scalar as Fr;
+    use filecoin_hashers::poseidon::{ColumnHasher, PoseidonHasher};
+    use generic_array::typenum::{U0, U2, U8};
+    use merkletree::store::VecStore;
+
+    use crate::merkle::MerkleTreeWrapper;
+
+    use super::fixtures::{column_roots, ColumnTree};
+
+    fn comm_c_matches_tree_c<Tree: 'static + MerkleTreeTrait>() {
+        let (roots, root) = column_roots::<Tree>();
+        let comm_c = comm_c_from_column_roots::<Tree>(&roots).expect("comm_c failure");
+        assert_eq!(comm_c, root);
+    }
+
+    #[test]
+    fn comm_c_from_fixed_column_roots() {
+        let roots: Vec<<PoseidonHasher as Hasher>::Domain> =
+            (0..8u64).map(|i| Fr::from(i).into()).collect();
+
+        let tree_c =
+            MerkleTreeWrapper::<PoseidonHasher, VecStore<_>, U8, U0, U0>::new(roots.clone())
+                .expect("tree_c failure");
+
+        let comm_c =
+            comm_c_from_column_roots::<ColumnTree<U8, U0, U0>>(&roots).expect("comm_c failure");
+        assert_eq!(comm_c, tree_c.root());
+    }
+
+    #[test]
+    fn comm_c_from_column_roots_base_8() {
+        comm_c_matches_tree_c::<ColumnTree<U8, U0, U0>>();
+    }
+
+    #[test]
+    fn comm_c_from_column_roots_sub_8_2() {
+        comm_c_matches_tree_c::<ColumnTree<U8, U2, U0>>();
+    }
+
+    #[test]
+    fn comm_c_from_column_roots_top_8_8_2() {
+        comm_c_matches_tree_c::<ColumnTree<U8, U8, U2>>();
+    }
+
+    #[test]
+    fn comm_c_from_column_roots_rejects_bad_count() {
+        let roots: Vec<<PoseidonHasher as Hasher>::Domain> =
+            (0..12u64).map(|i| Fr::from(i).into()).collect();
+
+        assert!(comm_c_from_column_roots::<ColumnTree<U8, U0, U0>>(&roots).is_err());
+    }
+
+    #[test]
+    fn column_hasher_matches_comm_c() {
+        type Tree = ColumnTree<U8, U0, U0>;
+
+        let (roots, _) = column_roots::<Tree>();
+        let comm_c = comm_c_from_column_roots::<Tree>(&roots).expect("comm_c failure");
+
+        let (last, init) = roots.split_last().expect("no column roots");
+        let mut hasher = ColumnHasher::<U8>::new();
+        for root in init {
+            hasher.update(*root);
+        }
+        assert!(hasher.finalize().is_err());
+
+        hasher.update(*last);
+        assert_eq!(hasher.finalize().expect("finalize failure"), comm_c);
+
+        // One more column starts a tree which can no longer be complete at this height.
+        hasher.update(roots[0]);
+        assert!(hasher.finalize().is_err());
+    }
+}
@@ -19,6 +19,7 @@ use rayon::prelude::{
 
 use crate::{
     error::Result,
+    measurements::{measure_op, Operation},
     multi_proof::MultiProof,
     parameter_cache::{CacheableParameters, ParameterSetMetadata},
     partitions::partition_count,
@@ -90,8 +91,9 @@ where
         ensure!(partition_count > 0, "There must be partitions");
 
         info!("vanilla_proofs:start");
-        let vanilla_proofs =
-            S::prove_all_partitions(&pub_params.vanilla_params, pub_in, priv_in, partition_count)?;
+        let vanilla_proofs = measure_op(Operation::VanillaProofs, || {
+            S::prove_all_partitions(&pub_params.vanilla_params, pub_in, priv_in, partition_count)
+        })?;
 
         info!("vanilla_proofs:finish");
 
@@ -100,13 +102,15 @@ where
         ensure!(sanity_check, "sanity check failed");
 
         info!("snark_proof:start");
-        let groth_proofs = Self::circuit_proofs(
-            pub_in,
-            vanilla_proofs,
-            &pub_params.vanilla_params,
-            groth_params,
-            pub_params.priority,
-        )?;
+        let groth_proofs = measure_op(Operation::CircuitProofs, || {
+            Self::circuit_proofs(
+                pub_in,
+                vanilla_proofs,
+                &pub_params.vanilla_params,
+                groth_params,
+                pub_params.priority,
+            )
+        })?;
         info!("snark_proof:finish");
 
         Ok(MultiProof::new(groth_proofs, &groth_params.pvk))
@@ -25,10 +25,18 @@ pub enum Error {
     MalformedInput,
     #[error("malformed merkle tree")]
     MalformedMerkleTree,
+    #[error("merkle path level {} has {} siblings, expected {}", _0, _2, _1)]
+    ArityMismatch(usize, usize, usize),
+    #[error("sector of {} nodes is not a tree of arities {}-{}-{}", _0, _1, _2, _3)]
+    TreeShapeMismatch(usize, usize, usize, usize),
     #[error("invalid input size")]
     InvalidInputSize,
     #[error("merkle tree generation error: {}", _0)]
     MerkleTreeGenerationError(String),
+    #[error("invalid merkle tree store: {}", _0)]
+    InvalidStore(String),
+    #[error("node {} has not been written yet", _0)]
+    NotYetAvailable(usize),
     #[error("Cannot (yet) generate inclusion proof for unaligned piece.")]
     UnalignedPiece,
     #[error("{}", _0)]
@@ -37,8 +45,16 @@ pub enum Error {
     Unclassified(String),
     #[error("Missing Private Input {0} for sector {1}")]
     MissingPrivateInput(&'static str, u64),
+    #[error("sector of {} nodes is too small for {} distinct challenges", _0, _1)]
+    SectorTooSmall(u64, usize),
+    #[error("no sectors to prove")]
+    NoSectors,
+    #[error("replica of {} bytes does not match the expected {} bytes", _1, _0)]
+    ReplicaSizeMismatch(usize, usize),
     #[error("faulty sectors {:?}", _0)]
     FaultySectors(Vec<SectorId>),
+    #[error("non-canonical field element: {}", _0)]
+    NonCanonicalElement(String),
     #[error("Invalid parameters file: {}", _0)]
     InvalidParameters(String),
 }
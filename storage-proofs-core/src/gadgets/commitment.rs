@@ -0,0 +1,132 @@
+use bellperson::{gadgets::num::AllocatedNum, ConstraintSystem, SynthesisError};
+use blstrs::Scalar as Fr;
+use filecoin_hashers::{HashFunction, Hasher, PoseidonArity};
+use generic_array::typenum::Unsigned;
+
+use crate::merkle::{get_base_tree_count, MerkleTreeTrait};
+
+/// Circuit version of `commitment::comm_c_from_column_roots`: computes the root of `tree_c` over
+/// the allocated column hashes `roots`, which must hold exactly one column hash per sector node,
+/// in node order.
+pub fn comm_c_from_column_roots<Tree, CS>(
+    mut cs: CS,
+    roots: &[AllocatedNum<Fr>],
+) -> Result<AllocatedNum<Fr>, SynthesisError>
+where
+    Tree: MerkleTreeTrait,
+    CS: ConstraintSystem<Fr>,
+{
+    let base_arity = Tree::Arity::to_usize();
+    let base_tree_count = get_base_tree_count::<Tree>();
+    if roots.len() < base_arity * base_tree_count || roots.len() % base_tree_count != 0 {
+        return Err(SynthesisError::Unsatisfiable);
+    }
+
+    let mut height = 0;
+    let mut nodes = roots.to_vec();
+    while nodes.len() > base_tree_count {
+        if nodes.len() % (base_arity * base_tree_count) != 0 {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+        nodes = hash_layer::<Tree::Hasher, Tree::Arity, _>(
+            cs.namespace(|| format!("base height {}", height)),
+            &nodes,
+            height,
+        )?;
+        height += 1;
+    }
+
+    if Tree::SubTreeArity::to_usize() > 0 {
+        nodes = hash_layer::<Tree::Hasher, Tree::SubTreeArity, _>(
+            cs.namespace(|| "sub tree"),
+            &nodes,
+            height,
+        )?;
+        height += 1;
+    }
+
+    if Tree::TopTreeArity::to_usize() > 0 {
+        nodes = hash_layer::<Tree::Hasher, Tree::TopTreeArity, _>(
+            cs.namespace(|| "top tree"),
+            &nodes,
+            height,
+        )?;
+    }
+
+    Ok(nodes.remove(0))
+}
+
+fn hash_layer<H, U, CS>(
+    mut cs: CS,
+    nodes: &[AllocatedNum<Fr>],
+    height: usize,
+) -> Result<Vec<AllocatedNum<Fr>>, SynthesisError>
+where
+    H: Hasher,
+    U: 'static + PoseidonArity,
+    CS: ConstraintSystem<Fr>,
+{
+    nodes
+        .chunks(U::to_usize())
+        .enumerate()
+        .map(|(i, children)| {
+            H::Function::hash_multi_leaf_circuit::<U, _>(
+                cs.namespace(|| format!("node {}", i)),
+                children,
+                height,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::util_cs::test_cs::TestConstraintSystem;
+    use filecoin_hashers::poseidon::PoseidonHasher;
+    use generic_array::typenum::{U0, U2, U8};
+
+    use crate::commitment::{
+        self,
+        fixtures::{column_roots, ColumnTree},
+    };
+
+    fn comm_c_circuit_matches<Tree: 'static + MerkleTreeTrait<Hasher = PoseidonHasher>>() {
+        let (roots, _) = column_roots::<Tree>();
+        let comm_c = commitment::comm_c_from_column_roots::<Tree>(&roots).expect("comm_c failure");
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let roots_num = roots
+            .iter()
+            .enumerate()
+            .map(|(i, root)| {
+                AllocatedNum::alloc(
+                    cs.namespace(|| format!("root {}", i)),
+                    || Ok((*root).into()),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .expect("alloc failure");
+
+        let comm_c_num = comm_c_from_column_roots::<Tree, _>(cs.namespace(|| "comm_c"), &roots_num)
+            .expect("synthesis failure");
+        comm_c_num
+            .inputize(cs.namespace(|| "comm_c input"))
+            .expect("inputize failure");
+
+        assert!(cs.is_satisfied());
+        assert_eq!(comm_c_num.get_value(), Some(comm_c.into()));
+        assert!(cs.verify(&[comm_c.into()]));
+    }
+
+    #[test]
+    fn comm_c_circuit_base_8() {
+        comm_c_circuit_matches::<ColumnTree<U8, U0, U0>>();
+    }
+
+    #[test]
+    fn comm_c_circuit_top_8_8_2() {
+        comm_c_circuit_matches::<ColumnTree<U8, U8, U2>>();
+    }
+}
@@ -1,3 +1,4 @@
+pub mod commitment;
 pub mod constraint;
 pub mod encode;
 pub mod insertion;
@@ -11,6 +11,7 @@ use bellperson::{
     Circuit, ConstraintSystem, SynthesisError,
 };
 use blstrs::Scalar as Fr;
+use ff::Field;
 use filecoin_hashers::{HashFunction, Hasher, PoseidonArity};
 use generic_array::typenum::Unsigned;
 
@@ -22,6 +23,7 @@ use crate::{
     parameter_cache::{CacheableParameters, ParameterSetMetadata},
     por::PoR,
     proof::ProofScheme,
+    util::scrub,
 };
 
 /// Proof of retrievability.
@@ -237,6 +239,35 @@ impl<H: Hasher, U: PoseidonArity, V: PoseidonArity, W: PoseidonArity> AuthPath<H
             top: SubPath { path: top },
         }
     }
+
+    /// Ascends the path from `leaf` through the base, sub and top trees, returning the computed
+    /// root and the path's index bits, leaves first. Nothing is enforced against the root.
+    pub fn synthesize_root<CS: ConstraintSystem<Fr>>(
+        self,
+        cs: &mut CS,
+        leaf: AllocatedNum<Fr>,
+    ) -> Result<(AllocatedNum<Fr>, Vec<Boolean>), SynthesisError> {
+        let (cur, mut bits) = self.base.synthesize(cs.namespace(|| "base"), leaf)?;
+        let (cur, sub_bits) = self.sub.synthesize(cs.namespace(|| "sub"), cur)?;
+        let (root, top_bits) = self.top.synthesize(cs.namespace(|| "top"), cur)?;
+        bits.extend(sub_bits);
+        bits.extend(top_bits);
+
+        Ok((root, bits))
+    }
+
+    /// Overwrites the path's sibling hashes in place.
+    pub fn zeroize(&mut self) {
+        for element in self.base.path.iter_mut() {
+            scrub(&mut element.hashes, Some(Fr::zero()));
+        }
+        for element in self.sub.path.iter_mut() {
+            scrub(&mut element.hashes, Some(Fr::zero()));
+        }
+        for element in self.top.path.iter_mut() {
+            scrub(&mut element.hashes, Some(Fr::zero()));
+        }
+    }
 }
 
 impl<Tree: MerkleTreeTrait> CircuitComponent for PoRCircuit<Tree> {
@@ -380,26 +411,9 @@ impl<Tree: MerkleTreeTrait> Circuit<Fr> for PoRCircuit<Tree> {
 
         {
             let value_num = value.allocated(cs.namespace(|| "value"))?;
-            let cur = value_num;
 
             // Ascend the merkle tree authentication path
-
-            // base tree
-            let (cur, base_auth_path_bits) =
-                auth_path.base.synthesize(cs.namespace(|| "base"), cur)?;
-
-            // sub
-            let (cur, sub_auth_path_bits) =
-                auth_path.sub.synthesize(cs.namespace(|| "sub"), cur)?;
-
-            // top
-            let (computed_root, top_auth_path_bits) =
-                auth_path.top.synthesize(cs.namespace(|| "top"), cur)?;
-
-            let mut auth_path_bits = Vec::new();
-            auth_path_bits.extend(base_auth_path_bits);
-            auth_path_bits.extend(sub_auth_path_bits);
-            auth_path_bits.extend(top_auth_path_bits);
+            let (computed_root, auth_path_bits) = auth_path.synthesize_root(cs, value_num)?;
 
             multipack::pack_into_inputs(cs.namespace(|| "path"), &auth_path_bits)?;
             {
@@ -597,3 +611,30 @@ where
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonHasher;
+    use generic_array::typenum::{U2, U8};
+
+    #[test]
+    fn test_auth_path_zeroize() {
+        let mut path = AuthPath::<PoseidonHasher, U8, U2, U2>::blank(256);
+        for element in path.base.path.iter_mut() {
+            element.hashes = vec![Some(Fr::one()); 7];
+        }
+        path.sub.path[0].hashes = vec![Some(Fr::one())];
+        path.top.path[0].hashes = vec![Some(Fr::one())];
+
+        path.zeroize();
+
+        let hashes = path.base.path.iter().map(|element| &element.hashes);
+        let hashes = hashes.chain(path.sub.path.iter().map(|element| &element.hashes));
+        let hashes = hashes.chain(path.top.path.iter().map(|element| &element.hashes));
+        for hashes in hashes {
+            assert!(hashes.iter().all(|hash| *hash == Some(Fr::zero())));
+        }
+    }
+}
@@ -241,4 +241,28 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_uint64_bit_orders() {
+        let value = 0x0123_4567_89ab_cdefu64;
+        let n = UInt64::constant(value);
+
+        let le: Vec<bool> = n
+            .to_bits_le()
+            .iter()
+            .map(|bit| bit.get_value().expect("constant bit"))
+            .collect();
+        let mut be: Vec<bool> = n
+            .to_bits_be()
+            .iter()
+            .map(|bit| bit.get_value().expect("constant bit"))
+            .collect();
+
+        assert!(le
+            .iter()
+            .enumerate()
+            .all(|(i, &bit)| bit == ((value >> i) & 1 == 1)));
+        be.reverse();
+        assert_eq!(le, be);
+    }
 }
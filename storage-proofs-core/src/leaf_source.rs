@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::future::{self, Future};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use anyhow::{ensure, Context};
+use blstrs::Scalar as Fr;
+use fr32::{try_fr_from_bytes_with, IdentityLeafTransform, LeafTransform};
+use memmap2::{Mmap, MmapOptions};
+
+use crate::{
+    error::{Error, Result},
+    util::{data_at_node, data_at_node_offset, NODE_SIZE},
+};
+
+/// A source of replica nodes for proving.
+pub trait LeafSource {
+    /// Returns the `NODE_SIZE` bytes of node `node`.
+    fn read_node(&self, node: usize) -> Result<Vec<u8>>;
+
+    /// Returns node `node` as an `Fr`, failing with `fr32::Error::NonCanonical` if its bytes are
+    /// not a canonical field element.
+    fn read_fr(&self, node: usize) -> Result<Fr> {
+        self.read_fr_with(node, &IdentityLeafTransform)
+    }
+
+    /// Like `read_fr`, for replicas whose leaves are stored encoded with `transform`.
+    fn read_fr_with(&self, node: usize, transform: &dyn LeafTransform) -> Result<Fr> {
+        Ok(try_fr_from_bytes_with(&self.read_node(node)?, transform)?)
+    }
+}
+
+impl LeafSource for [u8] {
+    fn read_node(&self, node: usize) -> Result<Vec<u8>> {
+        data_at_node(self, node).map(<[u8]>::to_vec)
+    }
+}
+
+/// Reads nodes of a replica file which is still being written front to back.
+///
+/// The writer shares `written` with its readers and advances it only once the nodes below it are
+/// durable, so that a challenge beyond that point fails with `Error::NotYetAvailable` instead of
+/// reading a partially written node.
+#[derive(Debug, Clone)]
+pub struct AppendOnlyLeafSource {
+    path: PathBuf,
+    written: Arc<AtomicUsize>,
+}
+
+impl AppendOnlyLeafSource {
+    pub fn new<P: AsRef<Path>>(path: P, written: Arc<AtomicUsize>) -> Self {
+        AppendOnlyLeafSource {
+            path: path.as_ref().to_path_buf(),
+            written,
+        }
+    }
+
+    /// Number of leading nodes which may be read.
+    pub fn written_nodes(&self) -> usize {
+        self.written.load(Ordering::Acquire)
+    }
+}
+
+impl LeafSource for AppendOnlyLeafSource {
+    fn read_node(&self, node: usize) -> Result<Vec<u8>> {
+        ensure!(node < self.written_nodes(), Error::NotYetAvailable(node));
+
+        let mut f = File::open(&self.path)
+            .with_context(|| format!("could not open replica {:?}", self.path))?;
+        f.seek(SeekFrom::Start(data_at_node_offset(node) as u64))?;
+
+        let mut buf = vec![0; NODE_SIZE];
+        f.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+}
+
+/// Reads nodes of a replica file through a read only memory map, so that only the pages holding
+/// challenged nodes are faulted in rather than the whole replica.
+#[derive(Debug)]
+pub struct MmapLeafSource {
+    path: PathBuf,
+    mmap: Mmap,
+}
+
+impl MmapLeafSource {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let f = File::open(&path).with_context(|| format!("could not open replica {:?}", path))?;
+        let mmap = unsafe { MmapOptions::new().map(&f) }
+            .with_context(|| format!("could not mmap replica {:?}", path))?;
+
+        Ok(MmapLeafSource { path, mmap })
+    }
+
+    /// Number of nodes in the mapped replica.
+    pub fn nodes(&self) -> usize {
+        self.mmap.len() / NODE_SIZE
+    }
+}
+
+impl LeafSource for MmapLeafSource {
+    fn read_node(&self, node: usize) -> Result<Vec<u8>> {
+        data_at_node(&self.mmap, node)
+            .map(<[u8]>::to_vec)
+            .with_context(|| format!("could not read node {} of replica {:?}", node, self.path))
+    }
+}
+
+/// The future returned by `AsyncLeafSource::read_node_async`.
+pub type LeafFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'a>>;
+
+/// A `LeafSource` for backends which are read asynchronously, e.g. a replica behind an object
+/// store.
+pub trait AsyncLeafSource {
+    /// Returns the `NODE_SIZE` bytes of node `node`.
+    fn read_node_async(&self, node: usize) -> LeafFuture<'_>;
+}
+
+impl AsyncLeafSource for [u8] {
+    fn read_node_async(&self, node: usize) -> LeafFuture<'_> {
+        Box::pin(future::ready(self.read_node(node)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::ptr;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    #[test]
+    fn test_append_only_leaf_source() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let path = dir.path().join("replica");
+        let mut writer = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .expect("open failure");
+
+        let written = Arc::new(AtomicUsize::new(0));
+        let source = AppendOnlyLeafSource::new(&path, written.clone());
+
+        writer
+            .write_all(&[1; 2 * NODE_SIZE])
+            .expect("write failure");
+        writer.sync_all().expect("sync failure");
+        written.store(2, Ordering::Release);
+
+        // The writer has started on node 2, but has not published it.
+        writer
+            .write_all(&[2; NODE_SIZE / 2])
+            .expect("write failure");
+
+        assert_eq!(
+            source.read_node(1).expect("read failure"),
+            vec![1; NODE_SIZE]
+        );
+
+        let err = source.read_node(2).expect_err("read an unwritten node");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotYetAvailable(2))
+        ));
+    }
+
+    #[test]
+    fn test_mmap_leaf_source() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let path = dir.path().join("replica");
+
+        // A 4 KiB replica whose nodes are filled with their own index.
+        let replica: Vec<u8> = (0..4096 / NODE_SIZE)
+            .flat_map(|node| vec![node as u8; NODE_SIZE])
+            .collect();
+        std::fs::write(&path, &replica).expect("write failure");
+
+        let source = MmapLeafSource::open(&path).expect("open failure");
+        assert_eq!(source.nodes(), 128);
+
+        for &node in &[0, 17, 64, 127] {
+            assert_eq!(
+                source.read_node(node).expect("read failure"),
+                replica.read_node(node).expect("read failure")
+            );
+        }
+        assert!(source.read_node(128).is_err());
+    }
+
+    #[test]
+    fn test_read_fr() {
+        let mut replica = vec![0; 2 * NODE_SIZE];
+        replica[0] = 7;
+        replica[NODE_SIZE..].copy_from_slice(&[0xff; NODE_SIZE]);
+
+        assert_eq!(replica.read_fr(0).expect("read failure"), Fr::from(7u64));
+
+        let err = replica.read_fr(1).expect_err("read a non-canonical node");
+        assert!(matches!(
+            err.downcast_ref::<fr32::Error>(),
+            Some(fr32::Error::NonCanonical { top_bits_set: true })
+        ));
+    }
+
+    #[test]
+    fn test_async_leaf_source() {
+        let replica: Vec<u8> = (0..4).flat_map(|node| [node as u8; NODE_SIZE]).collect();
+
+        // The in-memory future is ready on its first poll.
+        fn noop_raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                noop_raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+
+        match replica[..].read_node_async(1).as_mut().poll(&mut cx) {
+            Poll::Ready(node) => assert_eq!(node.expect("read failure"), vec![1; NODE_SIZE]),
+            Poll::Pending => panic!("in-memory read is pending"),
+        }
+        match replica[..].read_node_async(4).as_mut().poll(&mut cx) {
+            Poll::Ready(node) => {
+                let err = node.expect_err("read past the end");
+                assert!(matches!(
+                    err.downcast_ref::<Error>(),
+                    Some(Error::OutOfBounds(160, 128))
+                ));
+            }
+            Poll::Pending => panic!("in-memory read is pending"),
+        }
+    }
+}
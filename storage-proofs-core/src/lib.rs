@@ -13,12 +13,14 @@ use std::convert::TryInto;
 
 pub mod api_version;
 pub mod cache_key;
+pub mod commitment;
 pub mod compound_proof;
 pub mod crypto;
 pub mod data;
 pub mod drgraph;
 pub mod error;
 pub mod gadgets;
+pub mod leaf_source;
 pub mod measurements;
 pub mod merkle;
 pub mod multi_proof;
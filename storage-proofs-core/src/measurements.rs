@@ -1,4 +1,7 @@
 #[cfg(feature = "measurements")]
+use std::cell::RefCell;
+use std::sync::Arc;
+#[cfg(feature = "measurements")]
 use std::sync::{
     mpsc::{channel, Receiver, Sender},
     Mutex,
@@ -21,6 +24,14 @@ lazy_static! {
     };
 }
 
+/// Receives the wall time of each operation measured within `observe`.
+pub type Observer = Arc<dyn Fn(&Operation, Duration) + Send + Sync>;
+
+#[cfg(feature = "measurements")]
+thread_local! {
+    static OBSERVER: RefCell<Option<Observer>> = RefCell::new(None);
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct OpMeasurement {
@@ -44,6 +55,11 @@ pub enum Operation {
     PostFinalizeTicket,
     PostReadChallengedRange,
     PostPartialTicketHash,
+    PostGenerateTrees,
+    /// Generating the vanilla proofs of every partition of a compound proof.
+    VanillaProofs,
+    /// Synthesizing and proving the groth16 circuits of every partition of a compound proof.
+    CircuitProofs,
 }
 
 #[cfg(feature = "measurements")]
@@ -70,6 +86,13 @@ where
         .stop()
         .unwrap();
 
+    let wall_time = wall_start_time.elapsed();
+    OBSERVER.with(|observer| {
+        if let Some(observer) = observer.borrow().as_ref() {
+            observer(&op, wall_time);
+        }
+    });
+
     let opt_tx = OP_MEASUREMENTS
         .0
         .lock()
@@ -80,7 +103,7 @@ where
             .send(OpMeasurement {
                 op,
                 cpu_time: cpu_time_start.elapsed(),
-                wall_time: wall_start_time.elapsed(),
+                wall_time,
             })
             .expect("failed to send to perf channel");
     }
@@ -95,3 +118,32 @@ where
 {
     f()
 }
+
+/// Runs `f`, reporting each operation measured on this thread in the meantime to `observer`, e.g.
+/// so that a node can export proving phase timings. The `measurements` channel is fed as before.
+#[cfg(feature = "measurements")]
+pub fn observe<T, F>(observer: Option<Observer>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    struct Restore(Option<Observer>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            OBSERVER.with(|observer| *observer.borrow_mut() = prev);
+        }
+    }
+
+    let _restore = Restore(OBSERVER.with(|current| current.replace(observer)));
+    f()
+}
+
+/// Without the `measurements` feature nothing is measured, so `observer` is never called.
+#[cfg(not(feature = "measurements"))]
+pub fn observe<T, F>(_: Option<Observer>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    f()
+}
@@ -14,13 +14,14 @@ use merkletree::{
     },
     store::{DiskStore, ExternalReader, LevelCacheStore, ReplicaConfig, Store, StoreConfig},
 };
-use rand::Rng;
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
 use crate::{
     error::{Error, Result},
     merkle::{DiskTree, LCMerkleTree, LCStore, LCTree, MerkleTreeTrait, MerkleTreeWrapper},
-    util::{data_at_node, default_rows_to_discard, NODE_SIZE},
+    util::{data_at_node, default_rows_to_discard, validate_replica_len, NODE_SIZE},
 };
 
 // Create a DiskTree from the provided config(s), each representing a 'base' layer tree with 'base_tree_len' elements.
@@ -179,10 +180,7 @@ pub fn create_base_merkle_tree<Tree: MerkleTreeTrait>(
     size: usize,
     data: &[u8],
 ) -> Result<Tree> {
-    ensure!(
-        data.len() == (NODE_SIZE * size) as usize,
-        Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, size)
-    );
+    validate_replica_len(data, size)?;
 
     trace!("create_merkle_tree called with size {}", size);
     trace!(
@@ -381,10 +379,86 @@ pub fn get_base_tree_count<Tree: MerkleTreeTrait>() -> usize {
     }
 }
 
+/// Checks that a sector of `sector_nodes` nodes splits into `get_base_tree_count::<Tree>()` base
+/// trees, each a full tree of `Tree::Arity`. Otherwise every merkle path for the sector would have
+/// the wrong length, e.g. for a tree shape copied from another sector size.
+pub fn validate_arities<Tree: MerkleTreeTrait>(sector_nodes: usize) -> Result<()> {
+    let arity = Tree::Arity::to_usize();
+    let base_tree_count = get_base_tree_count::<Tree>();
+    let base_tree_nodes = sector_nodes / base_tree_count;
+
+    let mut full_nodes = arity;
+    while full_nodes < base_tree_nodes {
+        full_nodes *= arity;
+    }
+
+    ensure!(
+        sector_nodes % base_tree_count == 0 && full_nodes == base_tree_nodes,
+        Error::TreeShapeMismatch(
+            sector_nodes,
+            arity,
+            Tree::SubTreeArity::to_usize(),
+            Tree::TopTreeArity::to_usize(),
+        )
+    );
+
+    Ok(())
+}
+
 pub fn get_base_tree_leafs<Tree: MerkleTreeTrait>(base_tree_size: usize) -> Result<usize> {
     get_merkle_tree_leafs(base_tree_size, Tree::Arity::to_usize())
 }
 
+/// Locates `challenge` within a tree of shape `Tree` over `leafs` nodes, returning for each level
+/// of its inclusion path, leaves first, the index of the arity group containing the path node and
+/// the node's index within that group. The within-group indices match those of `gen_proof`'s
+/// path.
+pub fn challenge_coords<Tree: MerkleTreeTrait>(
+    challenge: usize,
+    leafs: usize,
+) -> Result<Vec<(usize, usize)>> {
+    let base_arity = Tree::Arity::to_usize();
+    let base_tree_count = get_base_tree_count::<Tree>();
+    let base_tree_leafs = leafs / base_tree_count;
+
+    ensure!(
+        base_tree_leafs >= base_arity && base_tree_leafs * base_tree_count == leafs,
+        Error::InvalidMerkleTreeArgs(leafs, base_tree_count, base_tree_leafs)
+    );
+    ensure!(
+        challenge < leafs,
+        "challenge {} is out of range for {} leafs",
+        challenge,
+        leafs
+    );
+
+    let mut coords = Vec::new();
+    let mut index = challenge % base_tree_leafs;
+    let mut width = base_tree_leafs;
+    while width > 1 {
+        ensure!(
+            width % base_arity == 0,
+            Error::InvalidMerkleTreeArgs(leafs, base_tree_count, base_tree_leafs)
+        );
+        coords.push((index / base_arity, index % base_arity));
+        index /= base_arity;
+        width /= base_arity;
+    }
+
+    let mut index = challenge / base_tree_leafs;
+    for arity in [
+        Tree::SubTreeArity::to_usize(),
+        Tree::TopTreeArity::to_usize(),
+    ] {
+        if arity > 0 {
+            coords.push((index / arity, index % arity));
+            index /= arity;
+        }
+    }
+
+    Ok(coords)
+}
+
 pub type ResTree<Tree> = MerkleTreeWrapper<
     <Tree as MerkleTreeTrait>::Hasher,
     <Tree as MerkleTreeTrait>::Store,
@@ -397,6 +471,7 @@ fn generate_base_tree<R: Rng, Tree: MerkleTreeTrait>(
     rng: &mut R,
     nodes: usize,
     temp_path: Option<PathBuf>,
+    on_base_tree: &mut dyn FnMut(usize),
 ) -> (Vec<u8>, ResTree<Tree>)
 where
     Tree::Store: 'static,
@@ -446,13 +521,14 @@ where
             }
         }
 
+        on_base_tree(nodes);
         (data, tree)
     } else {
-        (
-            data,
-            MerkleTreeWrapper::try_from_iter(elements.iter().map(|v| Ok(*v)))
-                .expect("try from iter map failure"),
-        )
+        let tree = MerkleTreeWrapper::try_from_iter(elements.iter().map(|v| Ok(*v)))
+            .expect("try from iter map failure");
+
+        on_base_tree(nodes);
+        (data, tree)
     }
 }
 
@@ -460,6 +536,7 @@ fn generate_sub_tree<R: Rng, Tree: MerkleTreeTrait>(
     rng: &mut R,
     nodes: usize,
     temp_path: Option<PathBuf>,
+    on_base_tree: &mut dyn FnMut(usize),
 ) -> (Vec<u8>, ResTree<Tree>)
 where
     Tree::Store: 'static,
@@ -473,7 +550,7 @@ where
         let (inner_data, tree) = generate_base_tree::<
             R,
             MerkleTreeWrapper<Tree::Hasher, Tree::Store, Tree::Arity>,
-        >(rng, base_tree_size, temp_path.clone());
+        >(rng, base_tree_size, temp_path.clone(), on_base_tree);
         trees.push(tree);
         data.extend(inner_data);
     }
@@ -493,6 +570,28 @@ pub fn generate_tree<Tree: MerkleTreeTrait, R: Rng>(
 where
     Tree::Store: 'static,
 {
+    generate_tree_with_progress::<Tree, R>(rng, nodes, temp_path, |_, _| {})
+}
+
+/// Like `generate_tree`, but reports `(nodes_done, nodes_total)` to `progress`: once with no nodes
+/// done, then each time a base tree has been built. The last call reports `nodes_total` nodes
+/// done. The callback does not influence the generated data or tree.
+pub fn generate_tree_with_progress<Tree: MerkleTreeTrait, R: Rng>(
+    rng: &mut R,
+    nodes: usize,
+    temp_path: Option<PathBuf>,
+    mut progress: impl FnMut(usize, usize),
+) -> (Vec<u8>, ResTree<Tree>)
+where
+    Tree::Store: 'static,
+{
+    let mut nodes_done = 0;
+    progress(nodes_done, nodes);
+    let on_base_tree: &mut dyn FnMut(usize) = &mut |base_tree_nodes| {
+        nodes_done += base_tree_nodes;
+        progress(nodes_done, nodes);
+    };
+
     let sub_tree_arity = Tree::SubTreeArity::to_usize();
     let top_tree_arity = Tree::TopTreeArity::to_usize();
 
@@ -508,7 +607,9 @@ where
             let (inner_data, tree) = generate_sub_tree::<
                 R,
                 MerkleTreeWrapper<Tree::Hasher, Tree::Store, Tree::Arity, Tree::SubTreeArity, U0>,
-            >(rng, nodes / top_tree_arity, temp_path.clone());
+            >(
+                rng, nodes / top_tree_arity, temp_path.clone(), on_base_tree
+            );
 
             sub_trees.push(tree);
             data.extend(inner_data);
@@ -518,8 +619,52 @@ where
             MerkleTreeWrapper::from_sub_trees(sub_trees).expect("from sub trees failure"),
         )
     } else if sub_tree_arity > 0 {
-        generate_sub_tree::<R, Tree>(rng, nodes, temp_path)
+        generate_sub_tree::<R, Tree>(rng, nodes, temp_path, on_base_tree)
     } else {
-        generate_base_tree::<R, Tree>(rng, nodes, temp_path)
+        generate_base_tree::<R, Tree>(rng, nodes, temp_path, on_base_tree)
     }
 }
+
+/// Like `generate_tree`, but drives generation from an `XorShiftRng` seeded with `seed`, so a
+/// failing replica can be reproduced anywhere from the seed alone. Passing `TEST_SEED` yields the
+/// same replica as the tests which seed their rng with it.
+pub fn generate_tree_from_seed<Tree: MerkleTreeTrait>(
+    seed: [u8; 16],
+    nodes: usize,
+    temp_path: Option<PathBuf>,
+) -> (Vec<u8>, ResTree<Tree>)
+where
+    Tree::Store: 'static,
+{
+    let mut rng = XorShiftRng::from_seed(seed);
+    generate_tree::<Tree, _>(&mut rng, nodes, temp_path)
+}
+
+/// Generates `count` trees of `nodes` nodes in parallel, as for the sectors of a window PoSt
+/// partition. Each tree is generated by `generate_tree_from_seed` from its own seed, drawn in order
+/// from `rng`, so the result does not depend on scheduling. Tree `i` is stored under
+/// `temp_path/sector-{i}`, if a `temp_path` is given.
+pub fn generate_trees<Tree: MerkleTreeTrait, R: Rng>(
+    rng: &mut R,
+    count: usize,
+    nodes: usize,
+    temp_path: Option<PathBuf>,
+) -> Vec<(Vec<u8>, ResTree<Tree>)>
+where
+    Tree::Store: 'static,
+{
+    let seeds: Vec<[u8; 16]> = (0..count).map(|_| rng.gen()).collect();
+
+    seeds
+        .into_par_iter()
+        .enumerate()
+        .map(|(i, seed)| {
+            let sector_path = temp_path.as_ref().map(|path| {
+                let sector_path = path.join(format!("sector-{}", i));
+                std::fs::create_dir_all(&sector_path).expect("create sector dir failure");
+                sector_path
+            });
+            generate_tree_from_seed::<Tree>(seed, nodes, sector_path)
+        })
+        .collect()
+}
@@ -0,0 +1,414 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context};
+use filecoin_hashers::{Domain, Hasher, PoseidonArity};
+use generic_array::typenum::{Unsigned, U0};
+use merkletree::{
+    hash::Algorithm,
+    store::{DiskStore, Store, StoreConfig},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    merkle::DiskTree,
+    util::NODE_SIZE,
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const ROWS_FILE: &str = "rows.dat";
+
+/// Number of arity groups hashed per chunk, bounding the memory a step uses to `CHUNK_GROUPS`
+/// groups of input nodes and `CHUNK_GROUPS` output nodes, whatever the sector size.
+const CHUNK_GROUPS: usize = 4096;
+
+/// Progress of a `DiskTreeBuilder`, persisted next to the checkpointed rows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Manifest {
+    /// Number of leaves in the tree being built.
+    leafs: usize,
+    arity: usize,
+    /// Number of rows above the leaves which have been written.
+    rows: usize,
+    /// Sha256 digest of the last written row.
+    row_digest: [u8; 32],
+    /// Sha256 digest of the leaves the first row was hashed from.
+    leaf_digest: [u8; 32],
+}
+
+/// Builds a base `DiskTree` one row at a time, checkpointing every completed row plus a small
+/// manifest under `dir`. Constructing a builder over a directory with an existing manifest
+/// resumes from the last checkpointed row, after checking that the manifest was written for the
+/// same tree and that the row it records is intact. The first step after resuming also checks that
+/// it is given the leaves the checkpointed rows were hashed from.
+///
+/// Completed rows are appended to a single checkpoint file and every row is hashed in chunks of
+/// `CHUNK_GROUPS` arity groups, so memory use is bounded independently of the sector size. `build`
+/// streams the leaves and the checkpointed rows into the tree's store and removes the
+/// checkpoints.
+#[derive(Debug)]
+pub struct DiskTreeBuilder<H: Hasher, U: PoseidonArity> {
+    dir: PathBuf,
+    manifest: Manifest,
+    /// Whether the leaves passed to this builder have been checked against the manifest.
+    leaves_checked: bool,
+    _h: PhantomData<(H, U)>,
+}
+
+impl<H: Hasher, U: PoseidonArity> DiskTreeBuilder<H, U> {
+    pub fn new<P: AsRef<Path>>(dir: P, leafs: usize) -> Result<Self> {
+        let arity = U::to_usize();
+        ensure!(arity > 1, "invalid tree arity {}", arity);
+        ensure!(
+            leafs >= arity && total_rows(leafs, arity).is_some(),
+            "{} leafs do not form a complete tree of arity {}",
+            leafs,
+            arity
+        );
+
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("could not create tree directory {:?}", dir))?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            let manifest: Manifest = serde_json::from_slice(&fs::read(&manifest_path)?)?;
+            ensure!(
+                manifest.leafs == leafs && manifest.arity == arity,
+                "manifest in {:?} is for {} leafs of arity {}, not {} leafs of arity {}",
+                dir,
+                manifest.leafs,
+                manifest.arity,
+                leafs,
+                arity
+            );
+            if manifest.rows > 0 {
+                let rows = File::open(dir.join(ROWS_FILE))?;
+                ensure!(
+                    rows.metadata()?.len() >= rows_len(leafs, arity, manifest.rows),
+                    "checkpointed rows in {:?} are shorter than their manifest",
+                    dir
+                );
+
+                let mut row = row_reader(rows, leafs, arity, manifest.rows)?;
+                let mut digest = Sha256::new();
+                io::copy(&mut row, &mut digest)?;
+                ensure!(
+                    <[u8; 32]>::from(digest.finalize()) == manifest.row_digest,
+                    "checkpointed row {} in {:?} does not match its manifest",
+                    manifest.rows,
+                    dir
+                );
+            }
+            manifest
+        } else {
+            Manifest {
+                leafs,
+                arity,
+                rows: 0,
+                row_digest: [0; 32],
+                leaf_digest: [0; 32],
+            }
+        };
+
+        Ok(DiskTreeBuilder {
+            dir,
+            manifest,
+            leaves_checked: false,
+            _h: PhantomData,
+        })
+    }
+
+    /// Number of rows above the leaves which have been checkpointed.
+    pub fn rows_completed(&self) -> usize {
+        self.manifest.rows
+    }
+
+    /// Number of rows above the leaves in the finished tree.
+    pub fn total_rows(&self) -> usize {
+        total_rows(self.manifest.leafs, self.manifest.arity).expect("checked in new")
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.rows_completed() == self.total_rows()
+    }
+
+    /// Hashes and checkpoints the next row of the tree over `data`. Returns whether rows remain.
+    pub fn step(&mut self, data: &[u8]) -> Result<bool> {
+        let Manifest {
+            leafs,
+            arity,
+            rows: height,
+            ..
+        } = self.manifest;
+        ensure!(
+            data.len() == leafs * NODE_SIZE,
+            Error::InvalidMerkleTreeArgs(data.len(), NODE_SIZE, leafs)
+        );
+        // Rows checkpointed from other leaves must not be mixed with rows hashed from these.
+        if height > 0 && !self.leaves_checked {
+            ensure!(
+                <[u8; 32]>::from(Sha256::digest(data)) == self.manifest.leaf_digest,
+                "leaves do not match those checkpointed in {:?}",
+                self.dir
+            );
+            self.leaves_checked = true;
+        }
+        if self.is_complete() {
+            return Ok(false);
+        }
+
+        // Drop anything a failed or interrupted step appended beyond the last checkpointed row.
+        let rows_path = self.dir.join(ROWS_FILE);
+        let mut rows = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&rows_path)
+            .with_context(|| format!("could not open {:?}", rows_path))?;
+        rows.set_len(rows_len(leafs, arity, height))?;
+        rows.seek(SeekFrom::End(0))?;
+
+        let mut prev: Box<dyn Read + '_> = if height == 0 {
+            Box::new(data)
+        } else {
+            Box::new(row_reader(File::open(&rows_path)?, leafs, arity, height)?)
+        };
+
+        let mut a = H::Function::default();
+        let mut digest = Sha256::new();
+        let mut buf = vec![0; CHUNK_GROUPS * arity * NODE_SIZE];
+        let mut row = Vec::with_capacity(CHUNK_GROUPS * NODE_SIZE);
+        let mut remaining = row_width(leafs, arity, height) * NODE_SIZE;
+        while remaining > 0 {
+            let chunk = &mut buf[..remaining.min(CHUNK_GROUPS * arity * NODE_SIZE)];
+            prev.read_exact(chunk)?;
+
+            row.clear();
+            for group in chunk.chunks(arity * NODE_SIZE) {
+                let children = group
+                    .chunks(NODE_SIZE)
+                    .map(H::Domain::try_from_bytes)
+                    .collect::<Result<Vec<_>>>()?;
+                a.reset();
+                row.extend_from_slice(AsRef::<[u8]>::as_ref(&a.multi_node(&children, height)));
+            }
+
+            digest.update(&row);
+            rows.write_all(&row)?;
+            remaining -= chunk.len();
+        }
+
+        // The row must be durable before the manifest claims it, so that a crash in between only
+        // leaves behind a partial row which the next attempt truncates.
+        rows.sync_all()?;
+
+        let mut manifest = self.manifest.clone();
+        manifest.rows += 1;
+        manifest.row_digest = digest.finalize().into();
+        if height == 0 {
+            manifest.leaf_digest = Sha256::digest(data).into();
+        }
+
+        let tmp_path = self.dir.join(format!("{}.tmp", MANIFEST_FILE));
+        write_synced(&tmp_path, &serde_json::to_vec(&manifest)?)?;
+        fs::rename(&tmp_path, self.dir.join(MANIFEST_FILE))?;
+        self.manifest = manifest;
+        self.leaves_checked = true;
+
+        Ok(!self.is_complete())
+    }
+
+    /// Checkpoints all remaining rows, then writes the tree into the store described by `config`
+    /// and removes the checkpoints, which the store supersedes.
+    pub fn build(mut self, data: &[u8], config: StoreConfig) -> Result<DiskTree<H, U, U0, U0>> {
+        while self.step(data)? {}
+
+        let Manifest {
+            leafs,
+            arity,
+            rows: height,
+            ..
+        } = self.manifest;
+        let rows_path = self.dir.join(ROWS_FILE);
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        {
+            let mut store = File::create(&data_path)
+                .with_context(|| format!("could not create store {:?}", data_path))?;
+            store.write_all(data)?;
+            io::copy(
+                &mut File::open(&rows_path)?.take(rows_len(leafs, arity, height)),
+                &mut store,
+            )?;
+            store.sync_all()?;
+        }
+
+        let tree_len = leafs + rows_len(leafs, arity, height) as usize / NODE_SIZE;
+        let store = DiskStore::new_from_disk(tree_len, arity, &config)?;
+        let tree = DiskTree::from_data_store(store, leafs)?;
+
+        fs::remove_file(self.dir.join(MANIFEST_FILE))?;
+        fs::remove_file(&rows_path)?;
+
+        Ok(tree)
+    }
+}
+
+/// Returns the number of rows above the leaves, or `None` if `leafs` is not a power of `arity`.
+fn total_rows(leafs: usize, arity: usize) -> Option<usize> {
+    let mut rows = 0;
+    let mut width = leafs;
+    while width > 1 {
+        if width % arity != 0 {
+            return None;
+        }
+        width /= arity;
+        rows += 1;
+    }
+    Some(rows)
+}
+
+/// Number of nodes in the row at `height`, the leaves being at height 0.
+fn row_width(leafs: usize, arity: usize, height: usize) -> usize {
+    leafs / arity.pow(height as u32)
+}
+
+/// Number of bytes taken by the rows from height 1 up to and including `height`.
+fn rows_len(leafs: usize, arity: usize, height: usize) -> u64 {
+    (1..=height)
+        .map(|h| (row_width(leafs, arity, h) * NODE_SIZE) as u64)
+        .sum()
+}
+
+/// Reads the checkpointed row at `height` from the rows file `rows`.
+fn row_reader(mut rows: File, leafs: usize, arity: usize, height: usize) -> Result<io::Take<File>> {
+    rows.seek(SeekFrom::Start(rows_len(leafs, arity, height - 1)))?;
+    Ok(rows.take((row_width(leafs, arity, height) * NODE_SIZE) as u64))
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut f = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonHasher;
+    use generic_array::typenum::U8;
+
+    use crate::{
+        merkle::{generate_tree_from_seed, MerkleTreeTrait},
+        util::default_rows_to_discard,
+        TEST_SEED,
+    };
+
+    type Tree = DiskTree<PoseidonHasher, U8, U0, U0>;
+
+    // 16 KiB of leaves.
+    const LEAFS: usize = 512;
+
+    #[test]
+    fn test_disk_tree_builder_resumes() {
+        let (data, fresh) = generate_tree_from_seed::<Tree>(TEST_SEED, LEAFS, None);
+
+        let checkpoints = tempfile::tempdir().expect("tempdir failure");
+        let mut builder =
+            DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).expect("new");
+        assert_eq!(builder.total_rows(), 3);
+        for _ in 0..2 {
+            assert!(builder.step(&data).expect("step failure"));
+        }
+        let rows_completed = builder.rows_completed();
+
+        // Simulate a crash partway through writing the next row: part of it was appended but the
+        // manifest never recorded it.
+        drop(builder);
+        let mut rows = OpenOptions::new()
+            .append(true)
+            .open(checkpoints.path().join(ROWS_FILE))
+            .expect("open failure");
+        rows.write_all(&[0xff; 7]).expect("write failure");
+
+        let builder =
+            DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).expect("resume");
+        assert_eq!(builder.rows_completed(), rows_completed);
+
+        let store = tempfile::tempdir().expect("tempdir failure");
+        let config = StoreConfig::new(
+            store.path(),
+            "resumed-tree".to_string(),
+            default_rows_to_discard(LEAFS, U8::to_usize()),
+        );
+        let resumed = builder.build(&data, config.clone()).expect("build failure");
+
+        assert_eq!(resumed.root(), fresh.root());
+        assert_eq!(
+            resumed.read_at(LEAFS - 1).expect("read failure"),
+            fresh.read_at(LEAFS - 1).expect("read failure")
+        );
+
+        // The store is on disk and supersedes the checkpoints.
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        assert_eq!(
+            fs::metadata(data_path).expect("metadata failure").len() as usize,
+            (LEAFS + 64 + 8 + 1) * NODE_SIZE
+        );
+        assert!(!checkpoints.path().join(ROWS_FILE).exists());
+        assert!(!checkpoints.path().join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_disk_tree_builder_rejects_mismatched_manifest() {
+        let (data, _) = generate_tree_from_seed::<Tree>(TEST_SEED, LEAFS, None);
+
+        let checkpoints = tempfile::tempdir().expect("tempdir failure");
+        let mut builder =
+            DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).expect("new");
+        builder.step(&data).expect("step failure");
+
+        // A different sector size must not resume from these checkpoints.
+        assert!(DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS * 8).is_err());
+
+        // Nor may a corrupted checkpoint.
+        let rows = checkpoints.path().join(ROWS_FILE);
+        let mut bytes = fs::read(&rows).expect("read failure");
+        bytes[0] ^= 1;
+        fs::write(&rows, bytes).expect("write failure");
+        assert!(DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).is_err());
+    }
+
+    #[test]
+    fn test_disk_tree_builder_rejects_other_leaves() {
+        let (data, fresh) = generate_tree_from_seed::<Tree>(TEST_SEED, LEAFS, None);
+
+        let checkpoints = tempfile::tempdir().expect("tempdir failure");
+        let mut builder =
+            DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).expect("new");
+        builder.step(&data).expect("step failure");
+        drop(builder);
+
+        // Resuming over different leaves must not mix their rows with the checkpointed ones.
+        let mut other = data.clone();
+        other[0] ^= 1;
+        let mut builder =
+            DiskTreeBuilder::<PoseidonHasher, U8>::new(checkpoints.path(), LEAFS).expect("resume");
+        assert!(builder.step(&other).is_err());
+        assert_eq!(builder.rows_completed(), 1);
+
+        let store = tempfile::tempdir().expect("tempdir failure");
+        let config = StoreConfig::new(
+            store.path(),
+            "resumed-tree".to_string(),
+            default_rows_to_discard(LEAFS, U8::to_usize()),
+        );
+        let resumed = builder.build(&data, config).expect("build failure");
+        assert_eq!(resumed.root(), fresh.root());
+    }
+}
@@ -2,17 +2,19 @@
 
 use std::fs::File;
 
-pub use merkletree::store::{DiskStore, ExternalReader, Store};
+pub use merkletree::store::{DiskStore, ExternalReader, MmapStore, Store};
 
 use filecoin_hashers::Hasher;
 use generic_array::typenum::{U0, U2, U4, U8};
 use merkletree::store::LevelCacheStore;
 
 mod builders;
+mod disk_builder;
 mod proof;
 mod tree;
 
 pub use builders::*;
+pub use disk_builder::*;
 pub use proof::*;
 pub use tree::*;
 
@@ -22,6 +24,10 @@ pub type MerkleStore<T> = DiskStore<T>;
 
 pub type DiskTree<H, U, V, W> = MerkleTreeWrapper<H, DiskStore<<H as Hasher>::Domain>, U, V, W>;
 pub type LCTree<H, U, V, W> = MerkleTreeWrapper<H, LCStore<<H as Hasher>::Domain>, U, V, W>;
+/// A tree which, when built without a `StoreConfig` (e.g. `generate_tree` with no temp path),
+/// keeps its nodes in an anonymous memory map instead of a file. Roots and proofs match those of
+/// the equivalent `DiskTree`.
+pub type MmapTree<H, U, V, W> = MerkleTreeWrapper<H, MmapStore<<H as Hasher>::Domain>, U, V, W>;
 
 pub type MerkleTree<H, U> = DiskTree<H, U, U0, U0>;
 pub type LCMerkleTree<H, U> = LCTree<H, U, U0, U0>;
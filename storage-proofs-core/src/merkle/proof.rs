@@ -11,7 +11,7 @@ use generic_array::typenum::{Unsigned, U0};
 use merkletree::hash::Algorithm;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::drgraph::graph_height;
+use crate::{drgraph::graph_height, error::Error, util::scrub};
 
 /// Trait to abstract over the concept of Merkle Proof.
 pub trait MerkleProofTrait: Clone + Serialize + DeserializeOwned + Debug + Sync + Send {
@@ -58,6 +58,16 @@ pub trait MerkleProofTrait: Clone + Serialize + DeserializeOwned + Debug + Sync
             .map(|v| (v.0.iter().copied().map(Into::into).collect(), v.1))
             .collect::<Vec<_>>()
     }
+    /// Returns every sibling of this proof's path as a field element, from the level below the
+    /// root down to the leaf, each level's siblings in path order. Suited to absorbing a proof into
+    /// a transcript without walking `path()` at every call site.
+    fn flatten_siblings(&self) -> Vec<Fr> {
+        self.path()
+            .into_iter()
+            .rev()
+            .flat_map(|(siblings, _)| siblings.into_iter().map(Into::into))
+            .collect()
+    }
     fn verify(&self) -> bool;
 
     /// Validates the MerkleProof and that it corresponds to the supplied node.
@@ -147,6 +157,61 @@ pub fn compound_tree_height<A: Unsigned, B: Unsigned, C: Unsigned>(leaves: usize
     a + b + c
 }
 
+/// Computes the root implied by `proof`'s leaf and path, without trusting the root the proof
+/// carries. A verifier holding `comm_c` and `comm_r` can check the result against
+/// `comm_r == hash2(comm_c, root)` instead of relying on a prover supplied `comm_r_last`.
+///
+/// The proof may come from an untrusted prover, so its path is checked against the tree's arities
+/// before it is walked: a path too short for the sub and top tree levels, a level with the wrong
+/// number of siblings or an index outside its level's arity is an error rather than a panic.
+pub fn root_from_proof<P: MerkleProofTrait>(proof: &P) -> Result<<P::Hasher as Hasher>::Domain> {
+    let path = proof.path();
+    let sub_levels = (P::SubTreeArity::to_usize() > 0) as usize;
+    let top_levels = (P::TopTreeArity::to_usize() > 0) as usize;
+    ensure!(
+        path.len() > sub_levels + top_levels,
+        Error::MalformedMerkleTree
+    );
+    let base_levels = path.len() - sub_levels - top_levels;
+
+    let mut a = <P::Hasher as Hasher>::Function::default();
+    let mut cur = proof.leaf();
+    let mut level = 0;
+    // As in `InclusionPath::root`, heights restart at the base of each sub and top tree.
+    for (levels, arity) in [
+        (&path[..base_levels], P::Arity::to_usize()),
+        (
+            &path[base_levels..base_levels + sub_levels],
+            P::SubTreeArity::to_usize(),
+        ),
+        (
+            &path[base_levels + sub_levels..],
+            P::TopTreeArity::to_usize(),
+        ),
+    ] {
+        for (height, (hashes, index)) in levels.iter().enumerate() {
+            ensure!(
+                hashes.len() + 1 == arity,
+                Error::ArityMismatch(level, arity - 1, hashes.len())
+            );
+            ensure!(
+                *index < arity,
+                "merkle path index {} out of range for arity {}",
+                index,
+                arity
+            );
+
+            a.reset();
+            let mut nodes = hashes.clone();
+            nodes.insert(*index, cur);
+            cur = a.multi_node(&nodes, height);
+            level += 1;
+        }
+    }
+
+    Ok(cur)
+}
+
 macro_rules! forward_method {
     ($caller:expr, $name:ident) => {
         match $caller {
@@ -212,6 +277,13 @@ impl<H: Hasher, Arity: PoseidonArity> InclusionPath<H, Arity> {
             .rev()
             .fold(0, |acc, p| (acc * Arity::to_usize()) + p.index)
     }
+
+    /// Overwrites the sibling hashes in place.
+    pub fn zeroize(&mut self) {
+        for element in self.path.iter_mut() {
+            scrub(&mut element.hashes, H::Domain::default());
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -295,6 +367,35 @@ impl<
     }
 }
 
+impl<H: Hasher, Arity: PoseidonArity, SubTreeArity: PoseidonArity, TopTreeArity: PoseidonArity>
+    MerkleProof<H, Arity, SubTreeArity, TopTreeArity>
+{
+    /// Overwrites the proof's leaf, root and path hashes in place, leaving it unverifiable.
+    pub fn zeroize(&mut self) {
+        let zero = H::Domain::default();
+        match &mut self.data {
+            ProofData::Single(proof) => {
+                scrub(std::slice::from_mut(&mut proof.leaf), zero);
+                scrub(std::slice::from_mut(&mut proof.root), zero);
+                proof.path.zeroize();
+            }
+            ProofData::Sub(proof) => {
+                scrub(std::slice::from_mut(&mut proof.leaf), zero);
+                scrub(std::slice::from_mut(&mut proof.root), zero);
+                proof.base_proof.zeroize();
+                proof.sub_proof.zeroize();
+            }
+            ProofData::Top(proof) => {
+                scrub(std::slice::from_mut(&mut proof.leaf), zero);
+                scrub(std::slice::from_mut(&mut proof.root), zero);
+                proof.base_proof.zeroize();
+                proof.sub_proof.zeroize();
+                proof.top_proof.zeroize();
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum ProofData<
     H: Hasher,
@@ -471,6 +572,74 @@ impl<
             data: ProofData::Single(SingleProof::new(path.into(), root, leaf)),
         }
     }
+
+    /// Reassembles a proof from its leaf, root and `path`, as returned by
+    /// `MerkleProofTrait::path`. Does not verify the proof.
+    pub fn from_parts(
+        leaf: H::Domain,
+        root: H::Domain,
+        path: &[(Vec<H::Domain>, usize)],
+    ) -> Result<Self> {
+        let sub_levels = (SubTreeArity::to_usize() > 0) as usize;
+        let top_levels = (TopTreeArity::to_usize() > 0) as usize;
+        ensure!(
+            path.len() > sub_levels + top_levels,
+            "merkle path of {} levels is too short",
+            path.len()
+        );
+
+        let base_levels = path.len() - sub_levels - top_levels;
+        let base_proof = inclusion_path::<H, BaseArity>(&path[..base_levels], 0)?;
+
+        let data = if top_levels > 0 {
+            ProofData::Top(TopProof::new(
+                base_proof,
+                inclusion_path(&path[base_levels..base_levels + 1], base_levels)?,
+                inclusion_path(&path[base_levels + 1..], base_levels + 1)?,
+                root,
+                leaf,
+            ))
+        } else if sub_levels > 0 {
+            ProofData::Sub(SubProof::new(
+                base_proof,
+                inclusion_path(&path[base_levels..], base_levels)?,
+                root,
+                leaf,
+            ))
+        } else {
+            ProofData::Single(SingleProof::new(base_proof, root, leaf))
+        };
+
+        Ok(MerkleProof { data })
+    }
+}
+
+/// Builds an `InclusionPath` from `path`, whose first level is at `height` in the full tree.
+fn inclusion_path<H: Hasher, Arity: PoseidonArity>(
+    path: &[(Vec<H::Domain>, usize)],
+    height: usize,
+) -> Result<InclusionPath<H, Arity>> {
+    path.iter()
+        .enumerate()
+        .map(|(i, (hashes, index))| {
+            ensure!(
+                hashes.len() + 1 == Arity::to_usize(),
+                Error::ArityMismatch(height + i, Arity::to_usize() - 1, hashes.len())
+            );
+            ensure!(
+                *index < Arity::to_usize(),
+                "merkle path index {} out of range for arity {}",
+                index,
+                Arity::to_usize()
+            );
+            Ok(PathElement {
+                hashes: hashes.clone(),
+                index: *index,
+                _arity: Default::default(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Into::into)
 }
 
 /// Converts a merkle_light proof to a SingleProof
@@ -701,12 +870,18 @@ mod tests {
 
     use filecoin_hashers::{
         blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher, Domain,
+        HashFunction,
     };
     use generic_array::typenum::{U2, U4, U8};
-    use rand::thread_rng;
-
-    use crate::merkle::{
-        generate_tree, get_base_tree_count, DiskStore, MerkleTreeTrait, MerkleTreeWrapper,
+    use rand::{thread_rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use crate::{
+        merkle::{
+            challenge_coords, generate_tree, generate_tree_from_seed, get_base_tree_count,
+            DiskStore, MerkleTreeTrait, MerkleTreeWrapper,
+        },
+        TEST_SEED,
     };
 
     fn merklepath<Tree: 'static + MerkleTreeTrait>() {
@@ -901,4 +1076,204 @@ mod tests {
             >,
         >();
     }
+
+    #[test]
+    fn generate_tree_from_seed_is_deterministic() {
+        type Tree = MerkleTreeWrapper<
+            PoseidonHasher,
+            DiskStore<<PoseidonHasher as Hasher>::Domain>,
+            U8,
+            U2,
+            U0,
+        >;
+        let nodes = 64 * get_base_tree_count::<Tree>();
+
+        let (data_a, tree_a) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        let (data_b, tree_b) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        assert_eq!(data_a, data_b);
+        assert_eq!(tree_a.root(), tree_b.root());
+
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (data_rng, tree_rng) = generate_tree::<Tree, _>(&mut rng, nodes, None);
+        assert_eq!(data_a, data_rng);
+        assert_eq!(tree_a.root(), tree_rng.root());
+
+        let mut other_seed = TEST_SEED;
+        other_seed[0] ^= 1;
+        let (data_c, _) = generate_tree_from_seed::<Tree>(other_seed, nodes, None);
+        assert_ne!(data_a, data_c);
+    }
+
+    #[test]
+    fn challenge_coords_32kib() {
+        // 32 KiB sectors use 8-8-2 trees: 16 base trees of 64 leafs each.
+        type Tree = MerkleTreeWrapper<
+            PoseidonHasher,
+            DiskStore<<PoseidonHasher as Hasher>::Domain>,
+            U8,
+            U8,
+            U2,
+        >;
+        let nodes = 1024;
+
+        // 777 is leaf 9 of base tree 12, and base tree 12 is the fifth in the second sub tree.
+        let coords = challenge_coords::<Tree>(777, nodes).expect("challenge_coords failure");
+        assert_eq!(coords, vec![(1, 1), (0, 1), (1, 4), (0, 1)]);
+
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        let proof = tree.gen_proof(777).expect("gen_proof failure");
+        let path_indices: Vec<_> = proof.path().iter().map(|(_, index)| *index).collect();
+        let coord_indices: Vec<_> = coords.iter().map(|(_, index)| *index).collect();
+        assert_eq!(path_indices, coord_indices);
+
+        assert!(challenge_coords::<Tree>(nodes, nodes).is_err());
+    }
+
+    type PoseidonDiskTree<U, V, W> =
+        MerkleTreeWrapper<PoseidonHasher, DiskStore<<PoseidonHasher as Hasher>::Domain>, U, V, W>;
+
+    fn root_from_proof_matches<Tree: 'static + MerkleTreeTrait>() {
+        let nodes = 64 * get_base_tree_count::<Tree>();
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+
+        for i in [0, 1, nodes / 2 + 3, nodes - 1] {
+            let proof = tree.gen_proof(i).expect("gen_proof failure");
+            assert_eq!(
+                root_from_proof(&proof).expect("root_from_proof failure"),
+                tree.root()
+            );
+        }
+    }
+
+    #[test]
+    fn flatten_siblings_top_8_4_2() {
+        type Tree = MerkleTreeWrapper<
+            PoseidonHasher,
+            DiskStore<<PoseidonHasher as Hasher>::Domain>,
+            U8,
+            U4,
+            U2,
+        >;
+
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, 512, None);
+        let proof = tree.gen_proof(300).expect("gen_proof failure");
+
+        let mut expected = Vec::new();
+        for (siblings, _) in proof.path().iter().rev() {
+            for sibling in siblings {
+                expected.push(Fr::from(*sibling));
+            }
+        }
+
+        let flattened = proof.flatten_siblings();
+        assert_eq!(flattened.len(), 7 + 7 + 3 + 1);
+        assert_eq!(flattened, expected);
+    }
+
+    #[test]
+    fn root_from_proof_base_8() {
+        root_from_proof_matches::<PoseidonDiskTree<U8, U0, U0>>();
+    }
+
+    #[test]
+    fn root_from_proof_top_8_8_2() {
+        root_from_proof_matches::<PoseidonDiskTree<U8, U8, U2>>();
+    }
+
+    #[test]
+    fn root_from_proof_checks_comm_r() {
+        type Tree = PoseidonDiskTree<U8, U2, U0>;
+        let nodes = 64 * get_base_tree_count::<Tree>();
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        let comm_c =
+            <PoseidonHasher as Hasher>::Domain::random(&mut XorShiftRng::from_seed(TEST_SEED));
+        let comm_r = <PoseidonHasher as Hasher>::Function::hash2(&comm_c, &tree.root());
+
+        // A proof whose carried root was tampered with still implies the real root.
+        let proof = tree.gen_proof(5).expect("gen_proof failure");
+        let mut parts = proof.path();
+        let forged =
+            MerkleProof::<PoseidonHasher, U8, U2, U0>::from_parts(proof.leaf(), comm_c, &parts)
+                .expect("from_parts failure");
+        assert_eq!(
+            <PoseidonHasher as Hasher>::Function::hash2(
+                &comm_c,
+                &root_from_proof(&forged).expect("root_from_proof failure")
+            ),
+            comm_r
+        );
+
+        // Changing a sibling changes the implied root, which then no longer matches comm_r.
+        parts[0].0[0] = comm_c;
+        let bad = MerkleProof::<PoseidonHasher, U8, U2, U0>::from_parts(
+            proof.leaf(),
+            tree.root(),
+            &parts,
+        )
+        .expect("from_parts failure");
+        assert_ne!(
+            <PoseidonHasher as Hasher>::Function::hash2(
+                &comm_c,
+                &root_from_proof(&bad).expect("root_from_proof failure")
+            ),
+            comm_r
+        );
+    }
+
+    #[test]
+    fn root_from_proof_rejects_bad_index() {
+        type Tree = PoseidonDiskTree<U8, U0, U0>;
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, 64, None);
+        let proof = tree.gen_proof(5).expect("gen_proof failure");
+
+        // A deserialized proof is not checked, so its index may exceed the level's arity.
+        let mut json = serde_json::to_value(&proof).expect("serialize failure");
+        json["data"]["Single"]["path"]["path"][0]["index"] = 9.into();
+        let bad: MerkleProof<PoseidonHasher, U8> =
+            serde_json::from_value(json).expect("deserialize failure");
+
+        let err = root_from_proof(&bad).expect_err("out of range index accepted");
+        assert_eq!(
+            err.to_string(),
+            "merkle path index 9 out of range for arity 8"
+        );
+    }
+
+    #[test]
+    fn root_from_proof_rejects_short_path() {
+        type Tree = PoseidonDiskTree<U8, U2, U0>;
+        let nodes = 64 * get_base_tree_count::<Tree>();
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        let proof = tree.gen_proof(5).expect("gen_proof failure");
+
+        let mut json = serde_json::to_value(&proof).expect("serialize failure");
+        json["data"]["Sub"]["base_proof"]["path"] = serde_json::json!([]);
+        json["data"]["Sub"]["sub_proof"]["path"] = serde_json::json!([]);
+        let bad: MerkleProof<PoseidonHasher, U8, U2, U0> =
+            serde_json::from_value(json).expect("deserialize failure");
+
+        let err = root_from_proof(&bad).expect_err("short path accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MalformedMerkleTree)
+        ));
+    }
+
+    #[test]
+    fn merkle_proof_zeroize() {
+        type Tree = PoseidonDiskTree<U8, U2, U0>;
+        let nodes = 64 * get_base_tree_count::<Tree>();
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, nodes, None);
+        let mut proof = tree.gen_proof(5).expect("gen_proof failure");
+
+        proof.zeroize();
+
+        let zero = <PoseidonHasher as Hasher>::Domain::default();
+        assert_eq!(proof.leaf(), zero);
+        assert_eq!(proof.root(), zero);
+        for (siblings, _) in proof.path() {
+            assert!(siblings.iter().all(|sibling| *sibling == zero));
+        }
+        assert!(!proof.verify());
+    }
 }
@@ -3,18 +3,23 @@
 use std::fmt::{self, Debug, Formatter};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::path::Path;
 
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use filecoin_hashers::{Hasher, PoseidonArity};
-use generic_array::typenum::U0;
+use generic_array::typenum::{Unsigned, U0};
 use merkletree::{
     hash::Hashable,
-    merkle::{FromIndexedParallelIterator, MerkleTree},
-    store::{ReplicaConfig, Store, StoreConfig},
+    merkle::{get_merkle_tree_len, FromIndexedParallelIterator, MerkleTree},
+    store::{DiskStore, ReplicaConfig, Store, StoreConfig},
 };
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator};
 
-use crate::merkle::{LCTree, MerkleProof, MerkleProofTrait};
+use crate::{
+    error::Error,
+    merkle::{LCTree, MerkleProof, MerkleProofTrait},
+    util::NODE_SIZE,
+};
 
 /// Trait used to abstract over the way Merkle Trees are constructed and stored.
 pub trait MerkleTreeTrait: Send + Sync + Debug {
@@ -307,6 +312,194 @@ impl<
     }
 }
 
+impl<H: Hasher, U: PoseidonArity> MerkleTreeWrapper<H, DiskStore<<H as Hasher>::Domain>, U> {
+    /// Checks that the store file described by `config`, which backs this base tree, holds exactly
+    /// the number of nodes the tree's height and arity require, and that the last of them, the
+    /// root, reads back intact. Run this on a tree opened from disk before generating proofs from
+    /// it.
+    pub fn validate_store(&self, config: &StoreConfig) -> Result<()> {
+        let arity = U::to_usize();
+        let leafs = self.inner.leafs();
+        let expected_len = get_merkle_tree_len(leafs, arity)?;
+
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        let file_len = std::fs::metadata(&data_path)
+            .with_context(|| format!("could not stat store {:?}", data_path))?
+            .len() as usize;
+        ensure!(
+            file_len == expected_len * NODE_SIZE,
+            Error::InvalidStore(format!(
+                "{} bytes for {} leafs of arity {}, expected {}",
+                file_len,
+                leafs,
+                arity,
+                expected_len * NODE_SIZE
+            ))
+        );
+
+        let root = self.inner.read_at(expected_len - 1).map_err(|err| {
+            Error::InvalidStore(format!("could not read node {}: {}", expected_len - 1, err))
+        })?;
+        ensure!(
+            root == self.inner.root(),
+            Error::InvalidStore("stored root does not match tree root".to_string())
+        );
+
+        Ok(())
+    }
+
+    /// Reads the top `cache_levels` rows of this tree, the root's row and the `cache_levels - 1`
+    /// rows below it, into memory, so that proofs generated through the returned tree only read
+    /// the lower rows from disk. With `0` levels every node is read from disk, as by `gen_proof`;
+    /// with at least `row_count()` levels the whole tree is held in memory.
+    pub fn with_cache_levels(&self, cache_levels: usize) -> Result<TopCachedTree<'_, H, U>> {
+        let cache_levels = cache_levels.min(self.inner.row_count());
+        let cached_len = (0..cache_levels)
+            .map(|level| U::to_usize().pow(level as u32))
+            .sum::<usize>();
+        let cached_start = self.inner.len() - cached_len;
+        let cached = if cached_len > 0 {
+            self.inner.read_range(cached_start..self.inner.len())?
+        } else {
+            Vec::new()
+        };
+
+        Ok(TopCachedTree {
+            tree: self,
+            cached_start,
+            cached,
+        })
+    }
+}
+
+/// A base `DiskTree` with its top rows held in memory, see
+/// `MerkleTreeWrapper::with_cache_levels`.
+#[derive(Debug)]
+pub struct TopCachedTree<'a, H: Hasher, U: PoseidonArity> {
+    tree: &'a MerkleTreeWrapper<H, DiskStore<<H as Hasher>::Domain>, U>,
+    /// Store index of the first cached node.
+    cached_start: usize,
+    cached: Vec<<H as Hasher>::Domain>,
+}
+
+impl<'a, H: Hasher, U: PoseidonArity> TopCachedTree<'a, H, U> {
+    fn read_node(&self, index: usize) -> Result<<H as Hasher>::Domain> {
+        if index >= self.cached_start {
+            Ok(self.cached[index - self.cached_start])
+        } else {
+            self.tree.inner.read_at(index)
+        }
+    }
+
+    /// Creates a merkle proof of the leaf at `index`, identical to the tree's `gen_proof`.
+    pub fn gen_proof(&self, index: usize) -> Result<MerkleProof<H, U>> {
+        let arity = U::to_usize();
+        let leafs = self.tree.inner.leafs();
+        ensure!(index < leafs, Error::OutOfBounds(index, leafs));
+
+        let mut path = Vec::new();
+        let (mut row_start, mut width, mut row_index) = (0, leafs, index);
+        while width > 1 {
+            let group_start = row_start + row_index / arity * arity;
+            let siblings = (0..arity)
+                .filter(|&i| i != row_index % arity)
+                .map(|i| self.read_node(group_start + i))
+                .collect::<Result<Vec<_>>>()?;
+            path.push((siblings, row_index % arity));
+
+            row_start += width;
+            width /= arity;
+            row_index /= arity;
+        }
+
+        MerkleProof::from_parts(self.read_node(index)?, self.tree.inner.root(), &path)
+    }
+}
+
+/// Infers the arity of the base tree stored in the `DiskStore` file at `data_path` from its size:
+/// a complete tree of arity `a` and height `h` holds `(a^(h+1) - 1) / (a - 1)` nodes, and for the
+/// supported arities no two trees share a node count. Use this to catch a store being opened
+/// with the wrong tree shape before its nodes are misread.
+///
+/// Only the base arity is detected. A compound tree's sub and top tree arities are not recorded
+/// in any one base tree store, so they must still be checked against the number of stores.
+pub fn detect_store_arity<P: AsRef<Path>>(data_path: P) -> Result<usize> {
+    let data_path = data_path.as_ref();
+    let size = std::fs::metadata(data_path)
+        .with_context(|| format!("could not stat store {:?}", data_path))?
+        .len() as usize;
+    ensure!(
+        size % NODE_SIZE == 0,
+        Error::InvalidStore(format!("{} bytes is not a whole number of nodes", size))
+    );
+
+    let nodes = size / NODE_SIZE;
+    let arities: Vec<usize> = [2, 4, 8, 16]
+        .iter()
+        .copied()
+        .filter(|&arity| {
+            let (mut width, mut len) = (1, 1);
+            while len < nodes {
+                width *= arity;
+                len += width;
+            }
+            nodes > 1 && len == nodes
+        })
+        .collect();
+
+    match arities[..] {
+        [arity] => Ok(arity),
+        _ => Err(Error::InvalidStore(format!(
+            "{} nodes do not form a complete tree of a single supported arity",
+            nodes
+        ))
+        .into()),
+    }
+}
+
+/// Where two trees over the same number of leafs first differ, as found by `first_diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeDiff {
+    /// The lowest leaf index at which the trees hold different leaves.
+    Leaf(usize),
+    /// The trees hold the same leaves but different roots, i.e. one has corrupt upper rows.
+    Root,
+}
+
+/// Returns where `a` and `b` first differ, or `None` if all leaves and the roots agree. Use this
+/// to find where a cached tree went stale when its root no longer matches a freshly built one.
+pub fn first_diff<H, S, U, V, W>(
+    a: &MerkleTreeWrapper<H, S, U, V, W>,
+    b: &MerkleTreeWrapper<H, S, U, V, W>,
+) -> Result<Option<TreeDiff>>
+where
+    H: 'static + Hasher,
+    S: Store<<H as Hasher>::Domain>,
+    U: 'static + PoseidonArity,
+    V: 'static + PoseidonArity,
+    W: 'static + PoseidonArity,
+{
+    let leafs = a.leafs();
+    ensure!(
+        leafs == b.leafs(),
+        "trees of {} and {} leafs cannot be compared",
+        leafs,
+        b.leafs()
+    );
+
+    for i in 0..leafs {
+        if a.read_at(i)? != b.read_at(i)? {
+            return Ok(Some(TreeDiff::Leaf(i)));
+        }
+    }
+
+    Ok(if a.root() == b.root() {
+        None
+    } else {
+        Some(TreeDiff::Root)
+    })
+}
+
 impl<
         H: Hasher,
         S: Store<<H as Hasher>::Domain>,
@@ -350,3 +543,285 @@ impl<
         &mut self.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+
+    use filecoin_hashers::{poseidon::PoseidonHasher, Domain};
+    use generic_array::typenum::{U0, U2, U4, U8};
+    use merkletree::store::VecStore;
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use crate::{
+        merkle::{
+            first_diff, generate_tree, generate_tree_from_seed, generate_tree_with_progress,
+            generate_trees, validate_arities, DiskTree, MmapTree, TreeDiff,
+        },
+        util::default_rows_to_discard,
+        TEST_SEED,
+    };
+
+    #[test]
+    fn test_validate_store_rejects_truncated_store() {
+        let leafs = 64;
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let elements: Vec<_> = (0..leafs)
+            .map(|_| <PoseidonHasher as Hasher>::Domain::random(&mut rng))
+            .collect();
+
+        let temp_dir = tempfile::tempdir().expect("tempdir failure");
+        let config = StoreConfig::new(
+            temp_dir.path(),
+            "validate-store".to_string(),
+            default_rows_to_discard(leafs, 8),
+        );
+        let tree = DiskTree::<PoseidonHasher, U8, U0, U0>::try_from_iter_with_config(
+            elements.into_iter().map(Ok),
+            config.clone(),
+        )
+        .expect("tree failure");
+        tree.validate_store(&config).expect("valid store rejected");
+
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .expect("open failure");
+        let len = file.metadata().expect("metadata failure").len();
+
+        // Trailing bytes beyond the root are caught by the file length alone.
+        file.set_len(len + 32).expect("extend failure");
+        assert!(
+            tree.validate_store(&config).is_err(),
+            "overlong store accepted"
+        );
+
+        // Drop the root from the end of the store file.
+        file.set_len(len - 32).expect("truncate failure");
+        assert!(
+            tree.validate_store(&config).is_err(),
+            "truncated store accepted"
+        );
+    }
+
+    fn build_disk_tree<U: PoseidonArity>(dir: &Path, leafs: usize) -> StoreConfig {
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let elements: Vec<_> = (0..leafs)
+            .map(|_| <PoseidonHasher as Hasher>::Domain::random(&mut rng))
+            .collect();
+
+        let config = StoreConfig::new(
+            dir,
+            format!("detect-arity-{}", U::to_usize()),
+            default_rows_to_discard(leafs, U::to_usize()),
+        );
+        DiskTree::<PoseidonHasher, U, U0, U0>::try_from_iter_with_config(
+            elements.into_iter().map(Ok),
+            config.clone(),
+        )
+        .expect("tree failure");
+
+        config
+    }
+
+    #[test]
+    fn test_detect_store_arity() {
+        let temp_dir = tempfile::tempdir().expect("tempdir failure");
+
+        let config = build_disk_tree::<U2>(temp_dir.path(), 64);
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        assert_eq!(detect_store_arity(&data_path).expect("detect failure"), 2);
+
+        let config = build_disk_tree::<U8>(temp_dir.path(), 64);
+        let data_path = StoreConfig::data_path(&config.path, &config.id);
+        assert_eq!(detect_store_arity(&data_path).expect("detect failure"), 8);
+
+        // One node short of the U8 tree matches no arity.
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&data_path)
+            .expect("open failure");
+        file.set_len(72 * NODE_SIZE as u64)
+            .expect("truncate failure");
+        assert!(detect_store_arity(&data_path).is_err());
+    }
+
+    #[test]
+    fn test_generate_trees() {
+        type Tree = DiskTree<PoseidonHasher, U8, U2, U0>;
+
+        let temp_dir = tempfile::tempdir().expect("tempdir failure");
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let trees =
+            generate_trees::<Tree, _>(&mut rng, 4, 128, Some(temp_dir.path().to_path_buf()));
+        assert_eq!(trees.len(), 4);
+
+        // The same trees, built one at a time from the same seeds.
+        let serial_dir = tempfile::tempdir().expect("tempdir failure");
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        for (data, tree) in &trees {
+            let (serial_data, serial_tree) = generate_tree_from_seed::<Tree>(
+                rng.gen(),
+                128,
+                Some(serial_dir.path().to_path_buf()),
+            );
+            assert_eq!(data, &serial_data);
+            assert_eq!(tree.root(), serial_tree.root());
+        }
+    }
+
+    #[test]
+    fn test_first_diff() {
+        type Tree = DiskTree<PoseidonHasher, U8, U0, U0>;
+
+        let (data, a) = generate_tree_from_seed::<Tree>(TEST_SEED, 64, None);
+        let (_, b) = generate_tree_from_seed::<Tree>(TEST_SEED, 64, None);
+        assert_eq!(first_diff(&a, &b).expect("first diff failure"), None);
+
+        let mut other_seed = TEST_SEED;
+        other_seed[0] ^= 1;
+        let (other_data, other) = generate_tree_from_seed::<Tree>(other_seed, 64, None);
+        let expected = data
+            .chunks(NODE_SIZE)
+            .zip(other_data.chunks(NODE_SIZE))
+            .position(|(x, y)| x != y)
+            .map(TreeDiff::Leaf);
+        assert!(expected.is_some());
+        assert_eq!(
+            first_diff(&a, &other).expect("first diff failure"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_first_diff_root() {
+        type Tree = MerkleTreeWrapper<
+            PoseidonHasher,
+            VecStore<<PoseidonHasher as Hasher>::Domain>,
+            U8,
+            U0,
+            U0,
+        >;
+
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let leaves: Vec<_> = (0..64)
+            .map(|_| <PoseidonHasher as Hasher>::Domain::random(&mut rng))
+            .collect();
+        let a = Tree::new(leaves).expect("tree failure");
+
+        // Same leaves, but a corrupt root.
+        let mut nodes = a.read_range(0, a.len()).expect("read range failure");
+        *nodes.last_mut().expect("empty tree") =
+            <PoseidonHasher as Hasher>::Domain::random(&mut rng);
+        let bytes: Vec<u8> = nodes.iter().flat_map(|node| node.into_bytes()).collect();
+        let b = Tree::from_tree_slice(&bytes, a.leafs()).expect("tree failure");
+
+        assert_eq!(
+            first_diff(&a, &b).expect("first diff failure"),
+            Some(TreeDiff::Root)
+        );
+    }
+
+    #[test]
+    fn test_with_cache_levels() {
+        type Tree = DiskTree<PoseidonHasher, U4, U0, U0>;
+
+        let (_, tree) = generate_tree_from_seed::<Tree>(TEST_SEED, 1024, None);
+        let rows = tree.row_count();
+        for &cache_levels in &[0, 1, 3, rows, rows + 1] {
+            let cached = tree
+                .with_cache_levels(cache_levels)
+                .expect("with cache levels failure");
+            for &i in &[0, 5, 511, 1023] {
+                let proof = cached.gen_proof(i).expect("gen proof failure");
+                let expected = tree.gen_proof(i).expect("gen proof failure");
+                assert!(proof.validate(i));
+                assert_eq!(proof.leaf(), expected.leaf());
+                assert_eq!(proof.root(), expected.root());
+                assert_eq!(proof.path(), expected.path());
+            }
+            assert!(cached.gen_proof(1024).is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate_arities() {
+        // 2 KiB and 32 KiB sectors.
+        validate_arities::<DiskTree<PoseidonHasher, U8, U0, U0>>(64).expect("2 KiB rejected");
+        validate_arities::<DiskTree<PoseidonHasher, U8, U2, U0>>(1024).expect("32 KiB rejected");
+
+        // The 32 KiB shape does not split a 2 KiB sector into full base trees.
+        let err = validate_arities::<DiskTree<PoseidonHasher, U8, U2, U0>>(64)
+            .expect_err("2 KiB accepted with sub trees");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TreeShapeMismatch(64, 8, 2, 0))
+        ));
+        assert!(validate_arities::<DiskTree<PoseidonHasher, U8, U0, U0>>(1024).is_err());
+    }
+
+    fn mmap_tree_matches_disk_tree<U, V, W>(nodes: usize)
+    where
+        U: 'static + PoseidonArity,
+        V: 'static + PoseidonArity,
+        W: 'static + PoseidonArity,
+    {
+        // Without a temp path both stores are anonymous: the disk tree sits in an unnamed temp
+        // file, the mmap tree in an anonymous mapping.
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (disk_data, disk_tree) =
+            generate_tree::<DiskTree<PoseidonHasher, U, V, W>, _>(&mut rng, nodes, None);
+
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (mmap_data, mmap_tree) =
+            generate_tree::<MmapTree<PoseidonHasher, U, V, W>, _>(&mut rng, nodes, None);
+
+        assert_eq!(mmap_data, disk_data);
+        assert_eq!(mmap_tree.root(), disk_tree.root());
+        for challenge in [0, 1, nodes / 2, nodes - 1] {
+            let mmap_proof = mmap_tree.gen_proof(challenge).expect("gen_proof failure");
+            let disk_proof = disk_tree.gen_proof(challenge).expect("gen_proof failure");
+            assert!(mmap_proof.verify());
+            assert_eq!(mmap_proof.leaf(), disk_proof.leaf());
+            assert_eq!(mmap_proof.root(), disk_proof.root());
+            assert_eq!(mmap_proof.path(), disk_proof.path());
+        }
+    }
+
+    #[test]
+    fn test_mmap_tree_matches_disk_tree_base_8() {
+        mmap_tree_matches_disk_tree::<U8, U0, U0>(64);
+    }
+
+    #[test]
+    fn test_mmap_tree_matches_disk_tree_top_8_8_2() {
+        mmap_tree_matches_disk_tree::<U8, U8, U2>(1024);
+    }
+
+    #[test]
+    fn test_generate_tree_with_progress() {
+        type Tree = DiskTree<PoseidonHasher, U8, U8, U2>;
+        let nodes = 1024;
+
+        let mut calls = Vec::new();
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (data, tree) =
+            generate_tree_with_progress::<Tree, _>(&mut rng, nodes, None, |done, total| {
+                calls.push((done, total))
+            });
+
+        // One call up front, then one per base tree.
+        let expected: Vec<_> = (0..=16).map(|i| (i * 64, nodes)).collect();
+        assert_eq!(calls, expected);
+        assert_eq!(calls.last(), Some(&(nodes, nodes)));
+
+        let mut rng = XorShiftRng::from_seed(TEST_SEED);
+        let (plain_data, plain_tree) = generate_tree::<Tree, _>(&mut rng, nodes, None);
+        assert_eq!(data, plain_data);
+        assert_eq!(tree.root(), plain_tree.root());
+    }
+}
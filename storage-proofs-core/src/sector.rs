@@ -3,7 +3,9 @@ use std::fmt::{self, Display, Formatter};
 
 use blstrs::Scalar as Fr;
 use byteorder::{ByteOrder, LittleEndian};
+use filecoin_hashers::Domain;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 /// An ordered set of `SectorId`s.
 pub type OrderedSectorSet = BTreeSet<SectorId>;
@@ -45,3 +47,34 @@ impl SectorId {
         buf
     }
 }
+
+/// Returns a short, stable fingerprint of a replica's `comm_r` for indexing and deduplication.
+///
+/// This is the first 16 bytes of `sha256(comm_r)`. It is a fingerprint only and is unrelated to
+/// the protocol `SectorId`, which it must never stand in for.
+pub fn sector_fingerprint<D: Domain>(comm_r: D) -> [u8; 16] {
+    let digest = Sha256::digest(comm_r.into_bytes());
+    let mut fingerprint = [0u8; 16];
+    fingerprint.copy_from_slice(&digest[..16]);
+    fingerprint
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::poseidon::PoseidonDomain;
+
+    #[test]
+    fn test_sector_fingerprint() {
+        let comm_r = PoseidonDomain::from(Fr::from(1u64));
+        let other = PoseidonDomain::from(Fr::from(2u64));
+
+        assert_eq!(sector_fingerprint(comm_r), sector_fingerprint(comm_r));
+        assert_ne!(sector_fingerprint(comm_r), sector_fingerprint(other));
+        assert_eq!(
+            sector_fingerprint(comm_r),
+            [1, 208, 250, 189, 37, 31, 203, 190, 43, 147, 180, 185, 39, 178, 106, 210]
+        );
+    }
+}
@@ -19,14 +19,29 @@ pub fn data_at_node_offset(v: usize) -> usize {
 
 /// Returns the byte slice representing one node (of uniform size, NODE_SIZE) at position v in data.
 pub fn data_at_node(data: &[u8], v: usize) -> anyhow::Result<&[u8]> {
-    let offset = data_at_node_offset(v);
+    // Node indices may come straight from untrusted challenges, so an offset past `usize::MAX`
+    // is reported as out of bounds rather than overflowing.
+    let end = v
+        .checked_mul(NODE_SIZE)
+        .and_then(|offset| offset.checked_add(NODE_SIZE))
+        .unwrap_or(usize::MAX);
 
+    ensure!(end <= data.len(), Error::OutOfBounds(end, data.len()));
+
+    Ok(&data[end - NODE_SIZE..end])
+}
+
+/// Checks that `replica` holds exactly `sector_nodes` nodes. Run this once where a replica enters
+/// an API, so that a truncated replica is reported as such instead of surfacing as an out of
+/// bounds node on whichever challenge first lands past its end.
+pub fn validate_replica_len(replica: &[u8], sector_nodes: usize) -> anyhow::Result<()> {
+    let expected = sector_nodes * NODE_SIZE;
     ensure!(
-        offset + NODE_SIZE <= data.len(),
-        Error::OutOfBounds(offset + NODE_SIZE, data.len())
+        replica.len() == expected,
+        Error::ReplicaSizeMismatch(expected, replica.len())
     );
 
-    Ok(&data[offset..offset + NODE_SIZE])
+    Ok(())
 }
 
 /// Converts bytes into their bit representation, in little endian format.
@@ -147,6 +162,15 @@ pub fn reverse_bit_numbering(bits: Vec<Boolean>) -> Vec<Boolean> {
         .collect()
 }
 
+/// Overwrites every value in `values` with `zero`. The writes are volatile, so the compiler does not
+/// elide them even when the memory is about to be freed.
+pub fn scrub<T: Copy>(values: &mut [T], zero: T) {
+    for value in values.iter_mut() {
+        unsafe { std::ptr::write_volatile(value, zero) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
 // If the tree is large enough to use the default value (per-arity), use it.  If it's too small to cache anything (i.e. not enough rows), don't discard any.
 pub fn default_rows_to_discard(leafs: usize, arity: usize) -> usize {
     let row_count = get_merkle_tree_row_count(leafs, arity);
@@ -192,6 +216,36 @@ mod tests {
 
     use crate::TEST_SEED;
 
+    #[test]
+    fn test_validate_replica_len() {
+        let replica = vec![0u8; 64 * NODE_SIZE];
+        validate_replica_len(&replica, 64).expect("valid replica rejected");
+
+        let err = validate_replica_len(&replica[..63 * NODE_SIZE], 64)
+            .expect_err("truncated replica accepted");
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ReplicaSizeMismatch(2048, 2016))
+        ));
+    }
+
+    #[test]
+    fn test_data_at_node_out_of_bounds() {
+        let data = vec![0u8; 2 * NODE_SIZE];
+        assert_eq!(
+            data_at_node(&data, 1).expect("node 1 rejected").len(),
+            NODE_SIZE
+        );
+
+        for v in [2, usize::MAX / NODE_SIZE, usize::MAX] {
+            let err = data_at_node(&data, v).expect_err("out of bounds node accepted");
+            assert!(matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::OutOfBounds(_, 64))
+            ));
+        }
+    }
+
     #[test]
     fn test_bytes_into_boolean_vec() {
         let mut cs = TestConstraintSystem::<Fr>::new();
@@ -0,0 +1,62 @@
+use bellperson::{
+    gadgets::boolean::{AllocatedBit, Boolean},
+    util_cs::test_cs::TestConstraintSystem,
+    ConstraintSystem,
+};
+use blstrs::Scalar as Fr;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{thread_rng, Rng};
+use storage_proofs_porep::drg::kdf;
+
+fn random_bits(len: usize) -> Vec<bool> {
+    let mut rng = thread_rng();
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+fn alloc_bits<CS: ConstraintSystem<Fr>>(mut cs: CS, bits: &[bool]) -> Vec<Boolean> {
+    bits.iter()
+        .enumerate()
+        .map(|(i, bit)| {
+            Boolean::from(
+                AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(*bit))
+                    .expect("alloc failure"),
+            )
+        })
+        .collect()
+}
+
+fn synthesize_kdf(id: &[bool], parents: &[Vec<bool>]) -> TestConstraintSystem<Fr> {
+    let mut cs = TestConstraintSystem::new();
+    let id = alloc_bits(cs.namespace(|| "id"), id);
+    let parents = parents
+        .iter()
+        .enumerate()
+        .map(|(i, parent)| alloc_bits(cs.namespace(|| format!("parent {}", i)), parent))
+        .collect();
+    kdf(cs.namespace(|| "kdf"), &id, parents, None, None).expect("kdf failure");
+    cs
+}
+
+fn kdf_circuit_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kdf-circuit");
+    group.sample_size(10);
+
+    for parent_count in [6, 8, 14] {
+        let id = random_bits(256);
+        let parents: Vec<_> = (0..parent_count).map(|_| random_bits(256)).collect();
+        let constraints = synthesize_kdf(&id, &parents).num_constraints();
+
+        group.bench_function(
+            format!(
+                "sha256-{}-parents-{}-constraints",
+                parent_count, constraints
+            ),
+            |b| b.iter(|| black_box(synthesize_kdf(&id, &parents))),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, kdf_circuit_benchmark);
+criterion_main!(benches);
@@ -245,8 +245,9 @@ impl<'a, H: 'static + Hasher> Circuit<Fr> for DrgPoRepCircuit<'a, H> {
     }
 }
 
-/// Key derivation function.
-fn kdf<Scalar, CS>(
+/// Key derivation function: hashes `id | window_index | node | parents` with sha256 into the
+/// encoding key.
+pub fn kdf<Scalar, CS>(
     mut cs: CS,
     id: &[Boolean],
     parents: Vec<Vec<Boolean>>,
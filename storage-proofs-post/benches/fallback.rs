@@ -0,0 +1,41 @@
+use bellperson::{util_cs::bench_cs::BenchCS, Circuit};
+use blstrs::Scalar as Fr;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use filecoin_hashers::poseidon::PoseidonHasher;
+use storage_proofs_core::{
+    api_version::ApiVersion, compound_proof::CompoundProof, merkle::OctMerkleTree,
+    proof::ProofScheme,
+};
+use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCompound, SetupParams};
+
+type Tree = OctMerkleTree<PoseidonHasher>;
+
+// Sweeps the per-sector challenge count away from the protocol's, to see how the circuit scales.
+// Not consensus relevant: proofs are only valid for the challenge count the network fixes.
+fn fallback_post_challenge_count_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fallback-post-challenges");
+    for challenge_count in [1, 10, 32, 128] {
+        let pub_params = FallbackPoSt::<Tree>::setup(&SetupParams {
+            sector_size: 2048,
+            challenge_count,
+            sector_count: 1,
+            api_version: ApiVersion::V1_1_0,
+        })
+        .unwrap();
+
+        group.bench_function(format!("synthesize-2kib-{}", challenge_count), |b| {
+            b.iter(|| {
+                let mut cs = BenchCS::<Fr>::new();
+                FallbackPoStCompound::<Tree>::blank_circuit(&pub_params)
+                    .synthesize(&mut cs)
+                    .unwrap();
+                black_box(cs.num_constraints())
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, fallback_post_challenge_count_benchmark);
+criterion_main!(benches);
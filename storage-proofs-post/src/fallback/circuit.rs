@@ -1,23 +1,36 @@
-use bellperson::{gadgets::num::AllocatedNum, Circuit, ConstraintSystem, SynthesisError};
+use std::marker::PhantomData;
+
+use anyhow::ensure;
+#[cfg(feature = "challenge-selector")]
+use bellperson::gadgets::{
+    boolean::{AllocatedBit, Boolean},
+    multipack,
+};
+use bellperson::{
+    gadgets::num::AllocatedNum, util_cs::bench_cs::BenchCS, Circuit, ConstraintSystem,
+    SynthesisError,
+};
 use blstrs::Scalar as Fr;
 use ff::Field;
-use filecoin_hashers::{HashFunction, Hasher};
+use filecoin_hashers::{HashFunction, Hasher, PoseidonArity};
+use generic_array::typenum::{Unsigned, U0};
 use rayon::prelude::{ParallelIterator, ParallelSlice};
 use storage_proofs_core::{
-    compound_proof::CircuitComponent,
-    error::Result,
+    commitment,
+    compound_proof::{CircuitComponent, CompoundProof},
+    error::{Error, Result},
     gadgets::{
-        constraint,
-        por::{AuthPath, PoRCircuit},
+        self, constraint,
+        por::{AuthPath, PoRCircuit, PoRCompound},
         variables::Root,
     },
-    merkle::MerkleTreeTrait,
+    merkle::{challenge_coords, DiskTree, MerkleProofTrait, MerkleTreeTrait},
     por,
     settings::SETTINGS,
-    util::NODE_SIZE,
+    util::{scrub, NODE_SIZE},
 };
 
-use crate::fallback::{PublicParams, PublicSector, SectorProof};
+use crate::fallback::{PublicParams, PublicSector, SectorProof, WeightedChallenges};
 
 /// This is the `FallbackPoSt` circuit.
 pub struct FallbackPoStCircuit<Tree: MerkleTreeTrait> {
@@ -38,6 +51,61 @@ impl<Tree: 'static + MerkleTreeTrait> Clone for FallbackPoStCircuit<Tree> {
     }
 }
 
+/// Entropy committed to by the prover. Only `H(preimage || preimage)` is made public, the
+/// preimage itself stays private.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Entropy {
+    pub preimage: Option<Fr>,
+}
+
+impl Entropy {
+    /// Computes the public commitment for the given entropy preimage, as enforced by the circuit.
+    pub fn commitment<H: Hasher>(preimage: H::Domain) -> H::Domain {
+        H::Function::hash2(&preimage, &preimage)
+    }
+
+    fn synthesize<H: Hasher, CS: ConstraintSystem<Fr>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<(), SynthesisError> {
+        let preimage_num = AllocatedNum::alloc(cs.namespace(|| "entropy_preimage"), || {
+            self.preimage.ok_or(SynthesisError::AssignmentMissing)
+        })?;
+
+        let commitment_num = H::Function::hash2_circuit(
+            cs.namespace(|| "entropy_commitment"),
+            &preimage_num,
+            &preimage_num,
+        )?;
+
+        commitment_num.inputize(cs.namespace(|| "entropy_commitment_input"))
+    }
+}
+
+/// A `FallbackPoStCircuit` which also binds prover-supplied `entropy`, whose commitment follows
+/// the circuit's own public inputs.
+pub struct EntropyCircuit<Tree: MerkleTreeTrait> {
+    pub circuit: FallbackPoStCircuit<Tree>,
+    pub entropy: Entropy,
+}
+
+impl<Tree: 'static + MerkleTreeTrait> Clone for EntropyCircuit<Tree> {
+    fn clone(&self) -> Self {
+        EntropyCircuit {
+            circuit: self.circuit.clone(),
+            entropy: self.entropy,
+        }
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for EntropyCircuit<Tree> {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.circuit.synthesize(cs)?;
+        self.entropy
+            .synthesize::<Tree::Hasher, _>(&mut cs.namespace(|| "entropy"))
+    }
+}
+
 pub struct Sector<Tree: MerkleTreeTrait> {
     pub comm_r: Option<Fr>,
     pub comm_c: Option<Fr>,
@@ -62,6 +130,68 @@ impl<Tree: MerkleTreeTrait> Clone for Sector<Tree> {
     }
 }
 
+impl<Tree: MerkleTreeTrait> Sector<Tree> {
+    /// Overwrites the sector's private field elements (`comm_c`, `comm_r_last`, the challenged
+    /// leafs and their paths) in place. With the `zeroize` feature enabled this also runs on drop,
+    /// so replica data does not linger in memory after proving.
+    pub fn zeroize(&mut self) {
+        scrub(&mut self.leafs, Some(Fr::zero()));
+        scrub(std::slice::from_mut(&mut self.comm_c), Some(Fr::zero()));
+        scrub(
+            std::slice::from_mut(&mut self.comm_r_last),
+            Some(Fr::zero()),
+        );
+        for path in self.paths.iter_mut() {
+            path.zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Tree: MerkleTreeTrait> Drop for Sector<Tree> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Converts a merkle proof's path into the circuit's `Option` nesting, checking that every level
+/// carries exactly the number of siblings required by the arity of its layer.
+#[allow(clippy::type_complexity)]
+pub fn path_to_circuit<P: MerkleProofTrait>(
+    proof: &P,
+) -> Result<Vec<(Vec<Option<Fr>>, Option<usize>)>> {
+    let base_arity = P::Arity::to_usize();
+    let sub_arity = P::SubTreeArity::to_usize();
+    let top_arity = P::TopTreeArity::to_usize();
+
+    let path = proof.path();
+    let upper_levels = [sub_arity, top_arity].iter().filter(|&&a| a > 0).count();
+    ensure!(path.len() > upper_levels, Error::MalformedMerkleTree);
+    let base_levels = path.len() - upper_levels;
+
+    path.into_iter()
+        .enumerate()
+        .map(|(height, (siblings, index))| {
+            let arity = if height < base_levels {
+                base_arity
+            } else if height == base_levels {
+                sub_arity
+            } else {
+                top_arity
+            };
+            ensure!(
+                siblings.len() == arity - 1,
+                Error::ArityMismatch(height, arity - 1, siblings.len())
+            );
+
+            Ok((
+                siblings.into_iter().map(|sib| Some(sib.into())).collect(),
+                Some(index),
+            ))
+        })
+        .collect()
+}
+
 impl<Tree: 'static + MerkleTreeTrait> Sector<Tree> {
     pub fn circuit(
         sector: &PublicSector<<Tree::Hasher as Hasher>::Domain>,
@@ -74,17 +204,90 @@ impl<Tree: 'static + MerkleTreeTrait> Sector<Tree> {
             .collect();
 
         let paths = vanilla_proof
-            .as_options()
-            .into_iter()
-            .map(Into::into)
-            .collect();
+            .inclusion_proofs()
+            .iter()
+            .map(|proof| path_to_circuit(proof).map(Into::into))
+            .collect::<Result<_>>()?;
+
+        Ok(Sector {
+            leafs,
+            id: Some(sector.id.into()),
+            comm_r: Some(sector.comm_r.into()),
+            comm_c: Some(vanilla_proof.comm_c.0.into()),
+            comm_r_last: Some(vanilla_proof.comm_r_last.0.into()),
+            paths,
+        })
+    }
+
+    /// Builds a sector from fully assembled `(challenge, leaf, siblings)` triples, without access
+    /// to the sector's tree. `siblings` holds the sibling values of each path level, leaves first;
+    /// the node's index within each level is derived from `challenge`.
+    #[allow(clippy::type_complexity)]
+    pub fn from_triples(
+        pub_params: &PublicParams,
+        sector: &PublicSector<<Tree::Hasher as Hasher>::Domain>,
+        comm_c: <Tree::Hasher as Hasher>::Domain,
+        comm_r_last: <Tree::Hasher as Hasher>::Domain,
+        triples: Vec<(u64, Fr, Vec<Vec<Option<Fr>>>)>,
+    ) -> Result<Self> {
+        ensure!(
+            triples.len() == pub_params.challenge_count,
+            "expected {} challenges, got {}",
+            pub_params.challenge_count,
+            triples.len()
+        );
+
+        let leaves = pub_params.sector_size as usize / NODE_SIZE;
+        let upper_arities: Vec<usize> = [
+            Tree::SubTreeArity::to_usize(),
+            Tree::TopTreeArity::to_usize(),
+        ]
+        .iter()
+        .copied()
+        .filter(|&arity| arity > 0)
+        .collect();
+
+        let mut leafs = Vec::with_capacity(triples.len());
+        let mut paths = Vec::with_capacity(triples.len());
+        for (challenge, leaf, siblings) in triples {
+            let coords = challenge_coords::<Tree>(challenge as usize, leaves)?;
+            ensure!(
+                siblings.len() == coords.len(),
+                "challenge {} needs {} path levels, got {}",
+                challenge,
+                coords.len(),
+                siblings.len()
+            );
+
+            let base_levels = coords.len() - upper_arities.len();
+            let path = siblings
+                .into_iter()
+                .zip(coords)
+                .enumerate()
+                .map(|(height, (siblings, (_, index)))| {
+                    let arity = if height < base_levels {
+                        Tree::Arity::to_usize()
+                    } else {
+                        upper_arities[height - base_levels]
+                    };
+                    ensure!(
+                        siblings.len() == arity - 1,
+                        Error::ArityMismatch(height, arity - 1, siblings.len())
+                    );
+                    Ok((siblings, Some(index)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            leafs.push(Some(leaf));
+            paths.push(path.into());
+        }
 
         Ok(Sector {
             leafs,
             id: Some(sector.id.into()),
             comm_r: Some(sector.comm_r.into()),
-            comm_c: Some(vanilla_proof.comm_c.into()),
-            comm_r_last: Some(vanilla_proof.comm_r_last.into()),
+            comm_c: Some(comm_c.into()),
+            comm_r_last: Some(comm_r_last.into()),
             paths,
         })
     }
@@ -113,6 +316,39 @@ impl<Tree: 'static + MerkleTreeTrait> Sector<Tree> {
 
 impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for &Sector<Tree> {
     fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        self.synthesize_with_comm_c(cs).map(|_| ())
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait> Sector<Tree> {
+    /// Synthesizes the sector, returning the allocated `comm_c` for callers which constrain it
+    /// further.
+    fn synthesize_with_comm_c<CS: ConstraintSystem<Fr>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<AllocatedNum<Fr>, SynthesisError> {
+        let (comm_c_num, comm_r_last_num) = self.synthesize_comm_r(cs)?;
+
+        // 2. Verify Inclusion Paths
+        for (i, (leaf, path)) in self.leafs.iter().zip(self.paths.iter()).enumerate() {
+            PoRCircuit::<Tree>::synthesize(
+                cs.namespace(|| format!("challenge_inclusion_{}", i)),
+                Root::Val(*leaf),
+                path.clone(),
+                Root::from_allocated::<CS>(comm_r_last_num.clone()),
+                true,
+            )?;
+        }
+
+        Ok(comm_c_num)
+    }
+
+    /// Inputizes `comm_r` and enforces `comm_r == H(comm_c || comm_r_last)`, returning the
+    /// allocated `comm_c` and `comm_r_last`.
+    fn synthesize_comm_r<CS: ConstraintSystem<Fr>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<(AllocatedNum<Fr>, AllocatedNum<Fr>), SynthesisError> {
         let Sector {
             comm_r,
             comm_c,
@@ -162,14 +398,305 @@ impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for &Sector<Tree> {
             );
         }
 
-        // 2. Verify Inclusion Paths
-        for (i, (leaf, path)) in leafs.iter().zip(paths.iter()).enumerate() {
-            PoRCircuit::<Tree>::synthesize(
-                cs.namespace(|| format!("challenge_inclusion_{}", i)),
-                Root::Val(*leaf),
-                path.clone(),
-                Root::from_allocated::<CS>(comm_r_last_num.clone()),
-                true,
+        Ok((comm_c_num, comm_r_last_num))
+    }
+}
+
+/// A sector whose challenges have been collapsed into `WeightedChallenges`. Each unique leaf is
+/// proven once and its weight is bound as a public input following the sector's own inputs.
+///
+/// The weights are not constrained in-circuit; `generate_public_inputs` derives them from the
+/// randomness and sector id, so a proof only verifies against the weights of the challenges the
+/// verifier actually drew.
+pub struct WeightedSector<Tree: MerkleTreeTrait> {
+    pub sector: Sector<Tree>,
+    pub weights: Vec<Option<Fr>>,
+}
+
+impl<Tree: MerkleTreeTrait> Clone for WeightedSector<Tree> {
+    fn clone(&self) -> Self {
+        WeightedSector {
+            sector: self.sector.clone(),
+            weights: self.weights.clone(),
+        }
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait> WeightedSector<Tree> {
+    /// `vanilla_proof` must hold one inclusion proof per unique challenge, in the order of
+    /// `weighted`.
+    pub fn circuit(
+        sector: &PublicSector<<Tree::Hasher as Hasher>::Domain>,
+        vanilla_proof: &SectorProof<Tree::Proof>,
+        weighted: &WeightedChallenges,
+    ) -> Result<Self> {
+        ensure!(
+            vanilla_proof.inclusion_proofs.len() == weighted.len(),
+            "expected {} inclusion proofs, one per unique challenge, got {}",
+            weighted.len(),
+            vanilla_proof.inclusion_proofs.len()
+        );
+        for (proof, w) in vanilla_proof.inclusion_proofs.iter().zip(weighted.iter()) {
+            ensure!(
+                proof.proves_challenge(w.challenge as usize),
+                "inclusion proof does not prove challenge {}",
+                w.challenge
+            );
+        }
+
+        Ok(WeightedSector {
+            sector: Sector::circuit(sector, vanilla_proof)?,
+            weights: weighted
+                .weights()
+                .into_iter()
+                .map(|w| Some(w.into()))
+                .collect(),
+        })
+    }
+
+    /// Public inputs for a `WeightedSector` proving `sector` under `randomness`. The challenges
+    /// and their weights are derived here rather than taken from the prover.
+    pub fn generate_public_inputs(
+        pub_params: &PublicParams,
+        randomness: <Tree::Hasher as Hasher>::Domain,
+        sector: &PublicSector<<Tree::Hasher as Hasher>::Domain>,
+    ) -> Result<Vec<Fr>> {
+        let weighted = WeightedChallenges::derive(pub_params, randomness, sector.id.into());
+        let por_pub_params = por::PublicParams {
+            leaves: pub_params.sector_size as usize / NODE_SIZE,
+            private: true,
+        };
+
+        let mut inputs = vec![sector.comm_r.into()];
+        for w in weighted.iter() {
+            let por_pub_inputs = por::PublicInputs {
+                commitment: None,
+                challenge: w.challenge as usize,
+            };
+            inputs.extend(PoRCompound::<Tree>::generate_public_inputs(
+                &por_pub_inputs,
+                &por_pub_params,
+                None,
+            )?);
+        }
+        inputs.extend(weighted.weights().into_iter().map(Fr::from));
+
+        Ok(inputs)
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for &WeightedSector<Tree> {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        assert_eq!(self.weights.len(), self.sector.leafs.len());
+
+        self.sector.synthesize(cs)?;
+
+        for (i, weight) in self.weights.iter().enumerate() {
+            let weight_num = AllocatedNum::alloc(cs.namespace(|| format!("weight_{}", i)), || {
+                weight.ok_or(SynthesisError::AssignmentMissing)
+            })?;
+            weight_num.inputize(cs.namespace(|| format!("weight_{}_input", i)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A sector whose `comm_c` is tied to a small set of public column roots: the circuit enforces
+/// that `comm_c` is the root of an arity `ColumnArity` tree over `column_roots`, as
+/// `commitment::comm_c_from_column_roots` derives it for `ColumnRootsTree`, and binds each root as
+/// a public input following the sector's own. The roots are typically one level of `tree_c`, e.g.
+/// the four roots beneath `comm_c` with `ColumnArity = U4`, so their number must be a power of
+/// `ColumnArity`.
+pub struct ColumnRootsSector<Tree: MerkleTreeTrait, ColumnArity: PoseidonArity> {
+    pub sector: Sector<Tree>,
+    pub column_roots: Vec<Option<Fr>>,
+    _c: PhantomData<ColumnArity>,
+}
+
+/// The shape of the tree `ColumnRootsSector` builds over its column roots.
+pub type ColumnRootsTree<H, ColumnArity> = DiskTree<H, ColumnArity, U0, U0>;
+
+impl<Tree: MerkleTreeTrait, ColumnArity: PoseidonArity> Clone
+    for ColumnRootsSector<Tree, ColumnArity>
+{
+    fn clone(&self) -> Self {
+        ColumnRootsSector {
+            sector: self.sector.clone(),
+            column_roots: self.column_roots.clone(),
+            _c: PhantomData,
+        }
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait, ColumnArity: 'static + PoseidonArity>
+    ColumnRootsSector<Tree, ColumnArity>
+{
+    /// Fails unless `vanilla_proof.comm_c` is the root of the tree over `column_roots`.
+    pub fn circuit(
+        sector: &PublicSector<<Tree::Hasher as Hasher>::Domain>,
+        vanilla_proof: &SectorProof<Tree::Proof>,
+        column_roots: &[<Tree::Hasher as Hasher>::Domain],
+    ) -> Result<Self> {
+        ensure!(
+            commitment::comm_c_from_column_roots::<ColumnRootsTree<Tree::Hasher, ColumnArity>>(
+                column_roots
+            )? == vanilla_proof.comm_c.0,
+            Error::InvalidCommitment
+        );
+
+        Ok(ColumnRootsSector {
+            sector: Sector::circuit(sector, vanilla_proof)?,
+            column_roots: column_roots
+                .iter()
+                .map(|root| Some((*root).into()))
+                .collect(),
+            _c: PhantomData,
+        })
+    }
+}
+
+impl<Tree: 'static + MerkleTreeTrait, ColumnArity: 'static + PoseidonArity> Circuit<Fr>
+    for &ColumnRootsSector<Tree, ColumnArity>
+{
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let comm_c_num = self.sector.synthesize_with_comm_c(cs)?;
+
+        let column_roots = self
+            .column_roots
+            .iter()
+            .enumerate()
+            .map(|(i, root)| {
+                let root_num =
+                    AllocatedNum::alloc(cs.namespace(|| format!("column_root_{}", i)), || {
+                        root.ok_or(SynthesisError::AssignmentMissing)
+                    })?;
+                root_num.inputize(cs.namespace(|| format!("column_root_{}_input", i)))?;
+                Ok(root_num)
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let column_roots_root = gadgets::commitment::comm_c_from_column_roots::<
+            ColumnRootsTree<Tree::Hasher, ColumnArity>,
+            _,
+        >(cs.namespace(|| "column_roots_tree"), &column_roots)?;
+        constraint::equal(
+            cs,
+            || "enforce_comm_c_column_roots_tree",
+            &comm_c_num,
+            &column_roots_root,
+        );
+
+        Ok(())
+    }
+}
+
+/// A sector which proves only the challenges marked active. Each challenge's path is still
+/// synthesized and its index bound, but its root is only checked against `comm_r_last` while its
+/// flag is set, so the leafs and paths of skipped challenges may be anything. The flags are bound
+/// as public inputs following the sector's own, so the verifier knows which challenges were
+/// proven. Such proofs are not accepted by the chain, hence the `challenge-selector` feature.
+#[cfg(feature = "challenge-selector")]
+pub struct SelectedSector<Tree: MerkleTreeTrait> {
+    pub sector: Sector<Tree>,
+    pub active: Vec<Option<bool>>,
+}
+
+#[cfg(feature = "challenge-selector")]
+impl<Tree: MerkleTreeTrait> Clone for SelectedSector<Tree> {
+    fn clone(&self) -> Self {
+        SelectedSector {
+            sector: self.sector.clone(),
+            active: self.active.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "challenge-selector")]
+impl<Tree: 'static + MerkleTreeTrait> SelectedSector<Tree> {
+    /// `active` holds one flag per challenge of `sector`.
+    pub fn new(sector: Sector<Tree>, active: &[bool]) -> Result<Self> {
+        ensure!(
+            active.len() == sector.leafs.len(),
+            "expected {} challenge flags, got {}",
+            sector.leafs.len(),
+            active.len()
+        );
+
+        Ok(SelectedSector {
+            sector,
+            active: active.iter().map(|&active| Some(active)).collect(),
+        })
+    }
+
+    /// Public inputs for a `SelectedSector` over a sector of `sector_nodes` leaves.
+    pub fn generate_public_inputs(
+        comm_r: <Tree::Hasher as Hasher>::Domain,
+        challenges: &[u64],
+        active: &[bool],
+        sector_nodes: usize,
+    ) -> Result<Vec<Fr>> {
+        ensure!(
+            active.len() == challenges.len(),
+            "expected {} challenge flags, got {}",
+            challenges.len(),
+            active.len()
+        );
+
+        let por_pub_params = por::PublicParams {
+            leaves: sector_nodes,
+            private: true,
+        };
+
+        let mut inputs = vec![comm_r.into()];
+        for &challenge in challenges {
+            let por_pub_inputs = por::PublicInputs {
+                commitment: None,
+                challenge: challenge as usize,
+            };
+            inputs.extend(PoRCompound::<Tree>::generate_public_inputs(
+                &por_pub_inputs,
+                &por_pub_params,
+                None,
+            )?);
+        }
+        inputs.extend(active.iter().map(|&active| Fr::from(active as u64)));
+
+        Ok(inputs)
+    }
+}
+
+#[cfg(feature = "challenge-selector")]
+impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for &SelectedSector<Tree> {
+    fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let Sector { leafs, paths, .. } = &self.sector;
+        assert_eq!(self.active.len(), leafs.len());
+
+        let (_, comm_r_last_num) = self.sector.synthesize_comm_r(cs)?;
+
+        let mut active_bits = Vec::with_capacity(self.active.len());
+        for (i, ((leaf, path), active)) in leafs.iter().zip(paths).zip(&self.active).enumerate() {
+            let cs = &mut cs.namespace(|| format!("challenge_inclusion_{}", i));
+
+            let leaf_num = Root::Val(*leaf).allocated(cs.namespace(|| "value"))?;
+            let (computed_root, path_bits) = path.clone().synthesize_root(cs, leaf_num)?;
+            multipack::pack_into_inputs(cs.namespace(|| "path"), &path_bits)?;
+
+            // (computed_root - comm_r_last) * active == 0
+            let active_bit = AllocatedBit::alloc(cs.namespace(|| "active"), *active)?;
+            cs.enforce(
+                || "enforce root is correct if active",
+                |lc| lc + computed_root.get_variable() - comm_r_last_num.get_variable(),
+                |lc| lc + active_bit.get_variable(),
+                |lc| lc,
+            );
+            active_bits.push(active_bit);
+        }
+
+        for (i, active_bit) in active_bits.into_iter().enumerate() {
+            multipack::pack_into_inputs(
+                cs.namespace(|| format!("active_{}_input", i)),
+                &[Boolean::from(active_bit)],
             )?;
         }
 
@@ -195,6 +722,16 @@ impl<Tree: 'static + MerkleTreeTrait> Circuit<Fr> for FallbackPoStCircuit<Tree>
 }
 
 impl<Tree: 'static + MerkleTreeTrait> FallbackPoStCircuit<Tree> {
+    /// Returns the number of constraints synthesized for `sectors` sectors under `pub_params`.
+    /// Only a single blank sector is synthesized, without a
+    /// witness, so this is cheap enough to size partitions with.
+    pub fn estimated_constraints(pub_params: &PublicParams, sectors: usize) -> Result<usize> {
+        let mut cs = BenchCS::<Fr>::new();
+        (&Sector::<Tree>::blank_circuit(pub_params)).synthesize(&mut cs)?;
+
+        Ok(cs.num_constraints() * sectors)
+    }
+
     fn synthesize_default<CS: ConstraintSystem<Fr>>(
         self,
         cs: &mut CS,
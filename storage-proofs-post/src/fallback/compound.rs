@@ -7,16 +7,19 @@ use filecoin_hashers::Hasher;
 use sha2::{Digest, Sha256};
 use storage_proofs_core::{
     compound_proof::{CircuitComponent, CompoundProof},
-    error::Result,
+    error::{Error, Result},
     gadgets::por::PoRCompound,
-    merkle::MerkleTreeTrait,
+    merkle::{validate_arities, MerkleTreeTrait},
     parameter_cache::{CacheableParameters, ParameterSetMetadata},
     por,
     proof::ProofScheme,
     util::NODE_SIZE,
 };
 
-use crate::fallback::{generate_leaf_challenge_inner, FallbackPoSt, FallbackPoStCircuit, Sector};
+use crate::fallback::{
+    generate_leaf_challenge_inner, FallbackPoSt, FallbackPoStCircuit, PublicInputs, PublicParams,
+    Sector,
+};
 
 pub struct FallbackPoStCompound<Tree>
 where
@@ -112,6 +115,11 @@ impl<'a, Tree: 'static + MerkleTreeTrait>
         partition_k: Option<usize>,
     ) -> Result<FallbackPoStCircuit<Tree>> {
         let num_sectors_per_chunk = pub_params.sector_count;
+        ensure!(
+            num_sectors_per_chunk > 0 && !pub_in.sectors.is_empty(),
+            Error::NoSectors
+        );
+        validate_arities::<Tree>(pub_params.sector_size as usize / NODE_SIZE)?;
         ensure!(
             pub_params.sector_count == vanilla_proof.sectors.len(),
             "vanilla proofs must equal sector_count: {} != {}",
@@ -160,3 +168,31 @@ impl<'a, Tree: 'static + MerkleTreeTrait>
         }
     }
 }
+
+impl<Tree: 'static + MerkleTreeTrait> FallbackPoStCompound<Tree> {
+    /// Returns the public inputs of every partition proof of each of `pub_inputs`, grouped by
+    /// proof: `packed[i][k]` holds the inputs of partition `k` of proof `i`. Flattening the outer
+    /// level gives the one input vector per circuit proof that groth16 batch verification takes.
+    pub fn pack_public_inputs(
+        pub_params: &PublicParams,
+        pub_inputs: &[PublicInputs<<Tree::Hasher as Hasher>::Domain>],
+        partitions: usize,
+    ) -> Result<Vec<Vec<Vec<Fr>>>> {
+        pub_inputs
+            .iter()
+            .map(|pub_inputs| {
+                (0..partitions)
+                    .map(|k| {
+                        <Self as CompoundProof<
+                            'static,
+                            FallbackPoSt<'static, Tree>,
+                            FallbackPoStCircuit<Tree>,
+                        >>::generate_public_inputs(
+                            pub_inputs, pub_params, Some(k)
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
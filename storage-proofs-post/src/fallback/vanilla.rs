@@ -1,10 +1,15 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::{TryFrom, TryInto};
+use std::iter;
 use std::marker::PhantomData;
+use std::ops::Range;
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
+use blake2b_simd::blake2b;
 use blstrs::Scalar as Fr;
 use byteorder::{ByteOrder, LittleEndian};
-use filecoin_hashers::{Domain, HashFunction, Hasher};
+use filecoin_hashers::{ct_eq, Domain, HashFunction, Hasher};
+use fr32::{bytes_into_fr, try_fr_from_bytes, IdentityLeafTransform, LeafTransform};
 use generic_array::typenum::Unsigned;
 use log::{error, trace};
 use rayon::prelude::{
@@ -15,11 +20,15 @@ use sha2::{Digest, Sha256};
 use storage_proofs_core::{
     api_version::ApiVersion,
     error::{Error, Result},
-    merkle::{MerkleProof, MerkleProofTrait, MerkleTreeTrait, MerkleTreeWrapper},
+    leaf_source::LeafSource,
+    merkle::{
+        base_path_length, challenge_coords, get_base_tree_count, MerkleProof, MerkleProofTrait,
+        MerkleTreeTrait, MerkleTreeWrapper,
+    },
     parameter_cache::ParameterSetMetadata,
     proof::ProofScheme,
     sector::SectorId,
-    util::{default_rows_to_discard, NODE_SIZE},
+    util::{default_rows_to_discard, scrub, NODE_SIZE},
 };
 
 #[derive(Debug, Clone)]
@@ -84,6 +93,113 @@ pub struct PublicSector<T: Domain> {
     pub comm_r: T,
 }
 
+impl<T: Domain> PublicSector<T> {
+    /// Decodes a sector's `comm_r` as read off chain, failing unless it is a canonical element of
+    /// `T`.
+    pub fn from_bytes(id: SectorId, comm_r: &[u8; 32]) -> Result<Self> {
+        Ok(PublicSector {
+            id,
+            comm_r: try_fr_from_bytes(comm_r)?.into(),
+        })
+    }
+}
+
+/// A sector's `comm_c`, typed apart from `CommRLast` so the two cannot be swapped. Serializes as
+/// the bare domain element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommC<T: Domain>(pub T);
+
+/// A sector's `comm_r_last`, the root of its replica tree. Serializes as the bare domain element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CommRLast<T: Domain>(pub T);
+
+/// Computes a sector's `comm_r` as `H(comm_c || comm_r_last)`.
+///
+/// ```
+/// use filecoin_hashers::poseidon::{PoseidonDomain, PoseidonHasher};
+/// use storage_proofs_post::fallback::{comm_r_from_parts, CommC, CommRLast};
+///
+/// let (comm_c, comm_r_last) = (PoseidonDomain::default(), PoseidonDomain::default());
+/// comm_r_from_parts::<PoseidonHasher>(CommC(comm_c), CommRLast(comm_r_last));
+/// ```
+///
+/// Passing the halves in the wrong order does not compile:
+///
+/// ```compile_fail
+/// use filecoin_hashers::poseidon::{PoseidonDomain, PoseidonHasher};
+/// use storage_proofs_post::fallback::{comm_r_from_parts, CommC, CommRLast};
+///
+/// let (comm_c, comm_r_last) = (PoseidonDomain::default(), PoseidonDomain::default());
+/// comm_r_from_parts::<PoseidonHasher>(CommRLast(comm_r_last), CommC(comm_c));
+/// ```
+pub fn comm_r_from_parts<H: Hasher>(
+    comm_c: CommC<H::Domain>,
+    comm_r_last: CommRLast<H::Domain>,
+) -> H::Domain {
+    H::Function::hash2(&comm_c.0, &comm_r_last.0)
+}
+
+/// Wire form of `PublicInputs` for a winning PoSt, with every field element byte encoded, so
+/// that verification requests can be decoded from serde or protobuf messages without knowing
+/// the domain type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinningPostRequest {
+    pub randomness: [u8; 32],
+    pub prover_id: [u8; 32],
+    pub sectors: Vec<WinningPostRequestSector>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinningPostRequestSector {
+    pub sector_id: u64,
+    pub comm_r: [u8; 32],
+}
+
+impl<T: Domain> TryFrom<WinningPostRequest> for PublicInputs<T> {
+    type Error = anyhow::Error;
+
+    /// Fails if any of the encoded values is not a valid element of `T`.
+    fn try_from(request: WinningPostRequest) -> Result<Self> {
+        let sectors = request
+            .sectors
+            .iter()
+            .map(|sector| PublicSector::from_bytes(sector.sector_id.into(), &sector.comm_r))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PublicInputs {
+            randomness: bytes_into_fr(&request.randomness)?.into(),
+            prover_id: bytes_into_fr(&request.prover_id)?.into(),
+            sectors,
+            k: None,
+        })
+    }
+}
+
+impl<T: Domain> From<&PublicInputs<T>> for WinningPostRequest {
+    fn from(pub_inputs: &PublicInputs<T>) -> Self {
+        WinningPostRequest {
+            randomness: pub_inputs.randomness.into_bytes()[..]
+                .try_into()
+                .expect("domain elements are 32 bytes"),
+            prover_id: pub_inputs.prover_id.into_bytes()[..]
+                .try_into()
+                .expect("domain elements are 32 bytes"),
+            sectors: pub_inputs
+                .sectors
+                .iter()
+                .map(|sector| WinningPostRequestSector {
+                    sector_id: sector.id.into(),
+                    comm_r: sector.comm_r.into_bytes()[..]
+                        .try_into()
+                        .expect("domain elements are 32 bytes"),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct PrivateSector<'a, Tree: MerkleTreeTrait> {
     pub tree: &'a MerkleTreeWrapper<
@@ -97,6 +213,47 @@ pub struct PrivateSector<'a, Tree: MerkleTreeTrait> {
     pub comm_r_last: <Tree::Hasher as Hasher>::Domain,
 }
 
+impl<'a, Tree: MerkleTreeTrait> PrivateSector<'a, Tree> {
+    /// Builds a `PrivateSector` whose `comm_r_last` is read from `tree`, so that it cannot
+    /// disagree with the tree the inclusion proofs are generated from.
+    pub fn from_tree(
+        tree: &'a MerkleTreeWrapper<
+            Tree::Hasher,
+            Tree::Store,
+            Tree::Arity,
+            Tree::SubTreeArity,
+            Tree::TopTreeArity,
+        >,
+        comm_c: <Tree::Hasher as Hasher>::Domain,
+    ) -> Self {
+        PrivateSector {
+            tree,
+            comm_c,
+            comm_r_last: tree.root(),
+        }
+    }
+
+    /// The public `comm_r` committed to by this sector.
+    pub fn comm_r(&self) -> <Tree::Hasher as Hasher>::Domain {
+        comm_r_from_parts::<Tree::Hasher>(CommC(self.comm_c), CommRLast(self.comm_r_last))
+    }
+
+    /// Overwrites `comm_c` and `comm_r_last` in place. With the `zeroize` feature enabled this also
+    /// runs on drop; `PrivateInputs` only borrows its sectors, so they are scrubbed with them.
+    pub fn zeroize(&mut self) {
+        let zero = <Tree::Hasher as Hasher>::Domain::default();
+        scrub(std::slice::from_mut(&mut self.comm_c), zero);
+        scrub(std::slice::from_mut(&mut self.comm_r_last), zero);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Tree: MerkleTreeTrait> Drop for PrivateSector<'_, Tree> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 #[derive(Debug)]
 pub struct PrivateInputs<'a, Tree: MerkleTreeTrait> {
     pub sectors: &'a [PrivateSector<'a, Tree>],
@@ -111,6 +268,50 @@ pub struct Proof<P: MerkleProofTrait> {
     pub sectors: Vec<SectorProof<P>>,
 }
 
+impl<P: MerkleProofTrait> Proof<P> {
+    /// One line description of this partition proof and the public sectors it is verified
+    /// against, for logging. Contains no private data.
+    pub fn summary(&self, pub_inputs: &PublicInputs<<P::Hasher as Hasher>::Domain>) -> String {
+        let inclusion_proof = self
+            .sectors
+            .first()
+            .and_then(|sector| sector.inclusion_proofs.first());
+        // Every level of an inclusion path holds all but one node of its arity group.
+        let sector_size = inclusion_proof.map_or(0, |proof| {
+            proof
+                .path()
+                .iter()
+                .map(|(hashes, _)| hashes.len() + 1)
+                .product::<usize>()
+                * NODE_SIZE
+        });
+        let challenge_count = self
+            .sectors
+            .first()
+            .map_or(0, |sector| sector.inclusion_proofs.len());
+        let partition = pub_inputs
+            .k
+            .map_or_else(|| "-".to_string(), |k| k.to_string());
+        let sectors = pub_inputs
+            .sectors
+            .iter()
+            .map(|sector| {
+                format!(
+                    "{}:{}",
+                    u64::from(sector.id),
+                    hex::encode(sector.comm_r.into_bytes())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!(
+            "sector_size={} partition={} challenges_per_sector={} sectors=[{}]",
+            sector_size, partition, challenge_count, sectors
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SectorProof<Proof: MerkleProofTrait> {
     #[serde(bound(
@@ -119,8 +320,8 @@ pub struct SectorProof<Proof: MerkleProofTrait> {
     ))]
     pub inclusion_proofs:
         Vec<MerkleProof<Proof::Hasher, Proof::Arity, Proof::SubTreeArity, Proof::TopTreeArity>>,
-    pub comm_c: <Proof::Hasher as Hasher>::Domain,
-    pub comm_r_last: <Proof::Hasher as Hasher>::Domain,
+    pub comm_c: CommC<<Proof::Hasher as Hasher>::Domain>,
+    pub comm_r_last: CommRLast<<Proof::Hasher as Hasher>::Domain>,
 }
 
 impl<P: MerkleProofTrait> SectorProof<P> {
@@ -135,6 +336,55 @@ impl<P: MerkleProofTrait> SectorProof<P> {
         self.inclusion_proofs[0].root()
     }
 
+    /// The `comm_r` this proof commits to, `H(comm_c || comm_r_last)`, computed from its own
+    /// fields. Comparing it with the claimed public `comm_r` is a cheap check to run before
+    /// verifying the inclusion proofs.
+    pub fn derived_comm_r(&self) -> <P::Hasher as Hasher>::Domain {
+        comm_r_from_parts::<P::Hasher>(self.comm_c, self.comm_r_last)
+    }
+
+    /// Overwrites `comm_c`, `comm_r_last` and the inclusion proofs, with their challenged leafs,
+    /// in place. With the `zeroize` feature enabled this also runs on drop.
+    pub fn zeroize(&mut self) {
+        let zero = <P::Hasher as Hasher>::Domain::default();
+        scrub(std::slice::from_mut(&mut self.comm_c.0), zero);
+        scrub(std::slice::from_mut(&mut self.comm_r_last.0), zero);
+        for proof in self.inclusion_proofs.iter_mut() {
+            proof.zeroize();
+        }
+    }
+
+    /// Checks that every domain element of this proof is a canonical field element. Domains
+    /// deserialized from untrusted bytes are not checked, and a non-canonical one would otherwise
+    /// only be caught when converted into an `Fr` for the circuit. Fails with
+    /// `Error::NonCanonicalElement` naming the first offending element.
+    pub fn validate_canonical(&self) -> Result<()> {
+        let check =
+            |domain: &<P::Hasher as Hasher>::Domain, what: &dyn Fn() -> String| -> Result<()> {
+                ensure!(
+                    try_fr_from_bytes(AsRef::<[u8]>::as_ref(domain)).is_ok(),
+                    Error::NonCanonicalElement(what())
+                );
+                Ok(())
+            };
+
+        check(&self.comm_c.0, &|| "comm_c".to_string())?;
+        check(&self.comm_r_last.0, &|| "comm_r_last".to_string())?;
+        for (i, proof) in self.inclusion_proofs.iter().enumerate() {
+            check(&proof.leaf(), &|| format!("leaf of inclusion proof {}", i))?;
+            check(&proof.root(), &|| format!("root of inclusion proof {}", i))?;
+            for (level, (siblings, _)) in proof.path().iter().enumerate() {
+                for (j, sibling) in siblings.iter().enumerate() {
+                    check(sibling, &|| {
+                        format!("sibling {} at level {} of inclusion proof {}", j, level, i)
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn commitments(&self) -> Vec<<P::Hasher as Hasher>::Domain> {
         self.inclusion_proofs
             .iter()
@@ -163,6 +413,197 @@ impl<P: MerkleProofTrait> SectorProof<P> {
     ) -> &Vec<MerkleProof<P::Hasher, P::Arity, P::SubTreeArity, P::TopTreeArity>> {
         &self.inclusion_proofs
     }
+
+    /// Encodes this proof as `comm_c || comm_r_last || inclusion proof count`, followed for each
+    /// inclusion proof by its leaf and then, per path level, the path node's index within its
+    /// arity group as one byte and the node's siblings. Field elements are written as their
+    /// `NODE_SIZE` little-endian bytes and the count as a little-endian `u32`.
+    ///
+    /// Inclusion proof roots are not encoded, as in a valid proof they all equal `comm_r_last`.
+    pub fn encode_compact(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(AsRef::<[u8]>::as_ref(&self.comm_c.0));
+        bytes.extend_from_slice(AsRef::<[u8]>::as_ref(&self.comm_r_last.0));
+        bytes.extend_from_slice(&(self.inclusion_proofs.len() as u32).to_le_bytes());
+
+        for proof in &self.inclusion_proofs {
+            bytes.extend_from_slice(AsRef::<[u8]>::as_ref(&proof.leaf()));
+            for (siblings, index) in proof.path() {
+                bytes.push(index as u8);
+                for sibling in &siblings {
+                    bytes.extend_from_slice(AsRef::<[u8]>::as_ref(sibling));
+                }
+            }
+        }
+
+        bytes
+    }
+
+    /// Decodes a proof written by `encode_compact` for a sector of `leaves` nodes.
+    pub fn decode_compact(bytes: &[u8], leaves: usize) -> Result<Self> {
+        let base_levels = base_path_length::<P::Arity, P::SubTreeArity, P::TopTreeArity>(leaves);
+        let mut arities = vec![P::Arity::to_usize(); base_levels];
+        for arity in [P::SubTreeArity::to_usize(), P::TopTreeArity::to_usize()] {
+            if arity > 0 {
+                arities.push(arity);
+            }
+        }
+
+        let mut reader = CompactReader(bytes);
+        let comm_c = reader.take_domain()?;
+        let comm_r_last = reader.take_domain()?;
+        let count = LittleEndian::read_u32(reader.take(4)?) as usize;
+
+        let mut inclusion_proofs = Vec::new();
+        for _ in 0..count {
+            let leaf = reader.take_domain()?;
+            let path = arities
+                .iter()
+                .map(|&arity| {
+                    let index = reader.take(1)?[0] as usize;
+                    let siblings = (1..arity)
+                        .map(|_| reader.take_domain())
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok((siblings, index))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            inclusion_proofs.push(MerkleProof::from_parts(leaf, comm_r_last, &path)?);
+        }
+        ensure!(
+            reader.0.is_empty(),
+            "compact sector proof has trailing bytes"
+        );
+
+        Ok(SectorProof {
+            inclusion_proofs,
+            comm_c: CommC(comm_c),
+            comm_r_last: CommRLast(comm_r_last),
+        })
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<P: MerkleProofTrait> Drop for SectorProof<P> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Reads consecutive fields of a `SectorProof::encode_compact` encoding.
+struct CompactReader<'a>(&'a [u8]);
+
+impl<'a> CompactReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "compact sector proof is truncated");
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn take_domain<T: Domain>(&mut self) -> Result<T> {
+        Ok(bytes_into_fr(self.take(NODE_SIZE)?)?.into())
+    }
+}
+
+/// The inclusion proofs of a `SectorProof` with every distinct sibling stored once.
+///
+/// Proofs of challenges close together in the tree share their upper siblings, so for many
+/// challenges most of the naive paths repeat the same nodes near the root. Here each path level
+/// refers to its siblings by index into `siblings`. `expand` rebuilds the original proofs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressedProofSet<P: MerkleProofTrait> {
+    #[serde(bound = "")]
+    pub root: <P::Hasher as Hasher>::Domain,
+    #[serde(bound = "")]
+    pub siblings: Vec<<P::Hasher as Hasher>::Domain>,
+    #[serde(bound = "")]
+    pub paths: Vec<CompressedPath<<P::Hasher as Hasher>::Domain>>,
+}
+
+/// A single inclusion proof of a `CompressedProofSet`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressedPath<T: Domain> {
+    #[serde(bound = "")]
+    pub leaf: T,
+    /// Per path level, the indices of its siblings in the shared pool and the path node's index
+    /// within its arity group.
+    pub levels: Vec<(Vec<u32>, u8)>,
+}
+
+impl<P: MerkleProofTrait> CompressedProofSet<P> {
+    /// Compresses `inclusion_proofs`, which must share a root.
+    pub fn new(
+        inclusion_proofs: &[MerkleProof<P::Hasher, P::Arity, P::SubTreeArity, P::TopTreeArity>],
+    ) -> Result<Self> {
+        ensure!(
+            !inclusion_proofs.is_empty(),
+            "no inclusion proofs to compress"
+        );
+        let root = inclusion_proofs[0].root();
+
+        let mut siblings = Vec::new();
+        let mut pool_index = HashMap::new();
+        let mut paths = Vec::with_capacity(inclusion_proofs.len());
+        for proof in inclusion_proofs {
+            ensure!(
+                proof.root() == root,
+                "inclusion proofs have different roots"
+            );
+            let levels = proof
+                .path()
+                .into_iter()
+                .map(|(level_siblings, index)| {
+                    let indices = level_siblings
+                        .into_iter()
+                        .map(|sibling| {
+                            *pool_index.entry(sibling).or_insert_with(|| {
+                                siblings.push(sibling);
+                                siblings.len() as u32 - 1
+                            })
+                        })
+                        .collect();
+                    (indices, index as u8)
+                })
+                .collect();
+            paths.push(CompressedPath {
+                leaf: proof.leaf(),
+                levels,
+            });
+        }
+
+        Ok(CompressedProofSet {
+            root,
+            siblings,
+            paths,
+        })
+    }
+
+    /// Rebuilds the inclusion proofs this set was compressed from, in order.
+    pub fn expand(
+        &self,
+    ) -> Result<Vec<MerkleProof<P::Hasher, P::Arity, P::SubTreeArity, P::TopTreeArity>>> {
+        self.paths
+            .iter()
+            .map(|path| {
+                let levels =
+                    path.levels
+                        .iter()
+                        .map(|(indices, index)| {
+                            let level_siblings = indices
+                                .iter()
+                                .map(|&i| {
+                                    self.siblings.get(i as usize).copied().with_context(|| {
+                                        format!("sibling index {} is out of range", i)
+                                    })
+                                })
+                                .collect::<Result<Vec<_>>>()?;
+                            Ok((level_siblings, *index as usize))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                MerkleProof::from_parts(path.leaf, self.root, &levels)
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -226,6 +667,281 @@ pub fn generate_leaf_challenges<T: Domain>(
     challenges
 }
 
+/// Parallel version of `generate_leaf_challenges`, for challenge counts large enough that deriving
+/// them serially is slow. Returns the same challenges, in the same order.
+pub fn generate_leaf_challenges_par<T: Domain>(
+    pub_params: &PublicParams,
+    randomness: T,
+    sector_id: u64,
+    challenge_count: usize,
+) -> Vec<u64> {
+    let mut hasher = Sha256::new();
+    hasher.update(AsRef::<[u8]>::as_ref(&randomness));
+    hasher.update(&sector_id.to_le_bytes()[..]);
+
+    (0..challenge_count)
+        .into_par_iter()
+        .map(|challenge_index| {
+            generate_leaf_challenge_inner::<T>(hasher.clone(), pub_params, challenge_index as u64)
+        })
+        .collect()
+}
+
+/// Generates the leaf challenges of each of `sector_ids` in parallel, e.g. when scanning many
+/// eligible sectors for a winning PoSt round. Entry `i` equals
+/// `generate_leaf_challenges(pub_params, randomness, sector_ids[i], challenge_count)`.
+pub fn generate_leaf_challenges_bulk<T: Domain>(
+    pub_params: &PublicParams,
+    randomness: T,
+    sector_ids: &[u64],
+    challenge_count: usize,
+) -> Vec<Vec<u64>> {
+    sector_ids
+        .par_iter()
+        .map(|&sector_id| {
+            generate_leaf_challenges(pub_params, randomness, sector_id, challenge_count)
+        })
+        .collect()
+}
+
+/// Selects how challenge randomness is mapped onto a leaf index within a sector.
+///
+/// Only `Sha256` is used by the protocol; other variants exist for research and benchmarking and
+/// must not be used to produce proofs which are verified on chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeHashToField {
+    /// `sha256(randomness || sector_id || challenge_index)`, where `sector_id` and
+    /// `challenge_index` are little-endian `u64`s. The first 8 bytes of the digest are read as a
+    /// little-endian `u64` and reduced modulo the number of leaves in the sector.
+    Sha256,
+    /// Same input layout and reduction as `Sha256`, but hashed with blake2b-512.
+    Blake2b,
+}
+
+impl Default for ChallengeHashToField {
+    fn default() -> Self {
+        ChallengeHashToField::Sha256
+    }
+}
+
+impl ChallengeHashToField {
+    /// Derives the leaf challenge at `leaf_challenge_index` for the given sector.
+    pub fn leaf_challenge<T: Domain>(
+        &self,
+        pub_params: &PublicParams,
+        randomness: T,
+        sector_id: u64,
+        leaf_challenge_index: u64,
+    ) -> u64 {
+        match self {
+            ChallengeHashToField::Sha256 => {
+                generate_leaf_challenge(pub_params, randomness, sector_id, leaf_challenge_index)
+            }
+            ChallengeHashToField::Blake2b => {
+                let mut data = Vec::with_capacity(NODE_SIZE + 16);
+                data.extend_from_slice(AsRef::<[u8]>::as_ref(&randomness));
+                data.extend_from_slice(&sector_id.to_le_bytes()[..]);
+                data.extend_from_slice(&leaf_challenge_index.to_le_bytes()[..]);
+
+                let hash = blake2b(&data);
+                let leaf_challenge = LittleEndian::read_u64(&hash.as_bytes()[..8]);
+
+                leaf_challenge % (pub_params.sector_size / NODE_SIZE as u64)
+            }
+        }
+    }
+}
+
+/// Generate all challenged leaf ranges for a single sector, using the given hash-to-field algorithm.
+pub fn generate_leaf_challenges_with<T: Domain>(
+    hash_to_field: ChallengeHashToField,
+    pub_params: &PublicParams,
+    randomness: T,
+    sector_id: u64,
+    challenge_count: usize,
+) -> Vec<u64> {
+    (0..challenge_count as u64)
+        .map(|challenge_index| {
+            hash_to_field.leaf_challenge(pub_params, randomness, sector_id, challenge_index)
+        })
+        .collect()
+}
+
+/// Derives `challenge_count` distinct leaf challenges for a single sector, drawing challenges in
+/// the order of `generate_leaf_challenges` and skipping any that repeat an earlier one.
+///
+/// Fails with `Error::SectorTooSmall` if the sector has fewer nodes than `challenge_count`. Proofs
+/// over these challenges are not accepted by the protocol's verifier.
+pub fn generate_distinct_leaf_challenges<T: Domain>(
+    pub_params: &PublicParams,
+    randomness: T,
+    sector_id: u64,
+    challenge_count: usize,
+) -> Result<Vec<u64>> {
+    let sector_nodes = pub_params.sector_size / NODE_SIZE as u64;
+    ensure!(
+        challenge_count as u64 <= sector_nodes,
+        Error::SectorTooSmall(sector_nodes, challenge_count)
+    );
+
+    let mut hasher = Sha256::new();
+    hasher.update(AsRef::<[u8]>::as_ref(&randomness));
+    hasher.update(&sector_id.to_le_bytes()[..]);
+
+    let mut seen = BTreeSet::new();
+    let mut challenges = Vec::with_capacity(challenge_count);
+    let mut challenge_index = 0;
+    while challenges.len() < challenge_count {
+        let challenge =
+            generate_leaf_challenge_inner::<T>(hasher.clone(), pub_params, challenge_index);
+        if seen.insert(challenge) {
+            challenges.push(challenge);
+        }
+        challenge_index += 1;
+    }
+
+    Ok(challenges)
+}
+
+/// Compares the leaf challenges derived for a single sector under two different randomness values.
+///
+/// Returns the sorted, deduplicated challenges as `(only_a, only_b, both)`. This is an analysis
+/// helper only and is not used when proving or verifying.
+pub fn challenge_set_diff<T: Domain>(
+    pub_params: &PublicParams,
+    randomness_a: T,
+    randomness_b: T,
+    sector_id: u64,
+    challenge_count: usize,
+) -> (Vec<u64>, Vec<u64>, Vec<u64>) {
+    let challenges_a: BTreeSet<u64> =
+        generate_leaf_challenges(pub_params, randomness_a, sector_id, challenge_count)
+            .into_iter()
+            .collect();
+    let challenges_b: BTreeSet<u64> =
+        generate_leaf_challenges(pub_params, randomness_b, sector_id, challenge_count)
+            .into_iter()
+            .collect();
+
+    let only_a = challenges_a.difference(&challenges_b).copied().collect();
+    let only_b = challenges_b.difference(&challenges_a).copied().collect();
+    let both = challenges_a.intersection(&challenges_b).copied().collect();
+
+    (only_a, only_b, both)
+}
+
+/// A unique challenged leaf together with the number of times it was drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedChallenge {
+    pub challenge: u64,
+    pub weight: u64,
+}
+
+/// A challenge set with duplicates collapsed, so each unique leaf is proven once while its
+/// multiplicity is carried as a weight. The total weight always equals the number of challenges
+/// drawn. Challenges keep the order in which they were first drawn.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightedChallenges(Vec<WeightedChallenge>);
+
+impl WeightedChallenges {
+    pub fn new(challenges: &[u64]) -> Self {
+        let mut weighted: Vec<WeightedChallenge> = Vec::with_capacity(challenges.len());
+        let mut index: HashMap<u64, usize> = HashMap::with_capacity(challenges.len());
+        for &challenge in challenges {
+            match index.get(&challenge) {
+                Some(&i) => weighted[i].weight += 1,
+                None => {
+                    index.insert(challenge, weighted.len());
+                    weighted.push(WeightedChallenge {
+                        challenge,
+                        weight: 1,
+                    });
+                }
+            }
+        }
+        WeightedChallenges(weighted)
+    }
+
+    /// Draws the `challenge_count` leaf challenges of `sector_id` from `randomness` and collapses
+    /// them, so the weights are the ones a verifier derives for itself.
+    pub fn derive<T: Domain>(pub_params: &PublicParams, randomness: T, sector_id: u64) -> Self {
+        Self::new(&generate_leaf_challenges(
+            pub_params,
+            randomness,
+            sector_id,
+            pub_params.challenge_count,
+        ))
+    }
+
+    pub fn challenges(&self) -> Vec<u64> {
+        self.0.iter().map(|w| w.challenge).collect()
+    }
+
+    pub fn weights(&self) -> Vec<u64> {
+        self.0.iter().map(|w| w.weight).collect()
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.0.iter().map(|w| w.weight).sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, WeightedChallenge> {
+        self.0.iter()
+    }
+}
+
+/// Returns the leaf challenges of every sector in partition `k`, one row per sector. `sector_ids`
+/// are the partition's sectors, in the order they are proven.
+pub fn generate_partition_challenges<T: Domain>(
+    pub_params: &PublicParams,
+    randomness: T,
+    k: usize,
+    sector_ids: &[SectorId],
+) -> Vec<Vec<u64>> {
+    sector_ids
+        .iter()
+        .enumerate()
+        .map(|(i, &sector_id)| {
+            let first = (k * pub_params.sector_count + i) * pub_params.challenge_count;
+            (first..first + pub_params.challenge_count)
+                .map(|challenge_index| {
+                    generate_leaf_challenge(
+                        pub_params,
+                        randomness,
+                        sector_id.into(),
+                        challenge_index as u64,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the sectors of partition `k` whose leaf challenges include `node`. `sector_ids` are the
+/// partition's sectors, in the order they are proven.
+pub fn sectors_challenging_node<T: Domain>(
+    pub_params: &PublicParams,
+    node: u64,
+    randomness: T,
+    sector_ids: &[SectorId],
+    k: usize,
+) -> Vec<SectorId> {
+    generate_partition_challenges(pub_params, randomness, k, sector_ids)
+        .iter()
+        .zip(sector_ids)
+        .filter(|(challenges, _)| challenges.contains(&node))
+        .map(|(_, &sector_id)| sector_id)
+        .collect()
+}
+
 /// Generates challenge, such that the range fits into the sector.
 pub fn generate_leaf_challenge<T: Domain>(
     pub_params: &PublicParams,
@@ -253,11 +969,39 @@ pub fn generate_leaf_challenge_inner<T: Domain>(
     leaf_challenge % (pub_params.sector_size / NODE_SIZE as u64)
 }
 
+/// A source of precomputed inclusion proofs, keyed by challenged leaf.
+pub trait ProofStore<P: MerkleProofTrait>: Sync {
+    fn get(&self, challenge: usize) -> Option<P>;
+}
+
+impl<P: MerkleProofTrait> ProofStore<P> for HashMap<usize, P> {
+    fn get(&self, challenge: usize) -> Option<P> {
+        HashMap::get(self, &challenge).cloned()
+    }
+}
+
 // Generates a single vanilla proof, given the private inputs and sector challenges.
 pub fn vanilla_proof<Tree: MerkleTreeTrait>(
     sector_id: SectorId,
     priv_inputs: &PrivateInputs<'_, Tree>,
     challenges: &[u64],
+) -> Result<Proof<Tree::Proof>> {
+    vanilla_proof_with_store(
+        sector_id,
+        priv_inputs,
+        challenges,
+        &HashMap::<usize, Tree::Proof>::new(),
+    )
+}
+
+/// Like `vanilla_proof`, but takes the inclusion proof for each challenge from `store` when it
+/// holds one, and only generates proofs from the tree for the remaining challenges. Stored proofs
+/// are checked exactly as generated ones are.
+pub fn vanilla_proof_with_store<Tree: MerkleTreeTrait, S: ProofStore<Tree::Proof>>(
+    sector_id: SectorId,
+    priv_inputs: &PrivateInputs<'_, Tree>,
+    challenges: &[u64],
+    store: &S,
 ) -> Result<Proof<Tree::Proof>> {
     ensure!(
         priv_inputs.sectors.len() == 1,
@@ -283,12 +1027,17 @@ pub fn vanilla_proof<Tree: MerkleTreeTrait>(
         .into_par_iter()
         .map(|challenged_leaf_index| {
             let challenged_leaf = challenges[challenged_leaf_index];
-            let proof = tree.gen_cached_proof(challenged_leaf as usize, Some(rows_to_discard))?;
+            let proof = match store.get(challenged_leaf as usize) {
+                Some(proof) => proof,
+                None => tree.gen_cached_proof(challenged_leaf as usize, Some(rows_to_discard))?,
+            };
 
             ensure!(
                 proof.validate(challenged_leaf as usize) && proof.root() == priv_sector.comm_r_last,
-                "Generated vanilla proof for sector {} is invalid",
-                sector_id
+                "Generated vanilla proof for sector {} is invalid at challenge {} (coords {:?})",
+                sector_id,
+                challenged_leaf,
+                challenge_coords::<Tree>(challenged_leaf as usize, tree_leafs).unwrap_or_default()
             );
 
             Ok(proof)
@@ -298,12 +1047,314 @@ pub fn vanilla_proof<Tree: MerkleTreeTrait>(
     Ok(Proof {
         sectors: vec![SectorProof {
             inclusion_proofs,
-            comm_c,
-            comm_r_last,
+            comm_c: CommC(comm_c),
+            comm_r_last: CommRLast(comm_r_last),
         }],
     })
 }
 
+/// The part of a replica needed to answer a known set of challenges: the inclusion proof, and with
+/// it the leaf, of each challenged node. Built once from the full tree, it can be stored offline
+/// and proven from later without the replica or its tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparseReplica<Proof: MerkleProofTrait> {
+    #[serde(bound(
+        serialize = "MerkleProof<Proof::Hasher, Proof::Arity, Proof::SubTreeArity, Proof::TopTreeArity>: Serialize",
+        deserialize = "MerkleProof<Proof::Hasher, Proof::Arity, Proof::SubTreeArity, Proof::TopTreeArity>: DeserializeOwned"
+    ))]
+    pub proofs: HashMap<
+        u64,
+        MerkleProof<Proof::Hasher, Proof::Arity, Proof::SubTreeArity, Proof::TopTreeArity>,
+    >,
+    pub comm_c: <Proof::Hasher as Hasher>::Domain,
+    pub comm_r_last: <Proof::Hasher as Hasher>::Domain,
+}
+
+impl<P: MerkleProofTrait> SparseReplica<P> {
+    /// Extracts the proofs for `challenges` from `priv_sector`'s tree.
+    pub fn from_tree<Tree: MerkleTreeTrait<Proof = P>>(
+        sector_id: SectorId,
+        priv_sector: &PrivateSector<'_, Tree>,
+        challenges: &[u64],
+    ) -> Result<Self> {
+        let priv_inputs = PrivateInputs {
+            sectors: std::slice::from_ref(priv_sector),
+        };
+        let mut proof = vanilla_proof(sector_id, &priv_inputs, challenges)?;
+        let mut sector_proof = proof.sectors.remove(0);
+
+        Ok(SparseReplica {
+            proofs: challenges
+                .iter()
+                .copied()
+                .zip(std::mem::take(&mut sector_proof.inclusion_proofs))
+                .collect(),
+            comm_c: sector_proof.comm_c.0,
+            comm_r_last: sector_proof.comm_r_last.0,
+        })
+    }
+
+    /// Like `vanilla_proof`, but answers `challenges` from the stored proofs alone. Fails if any
+    /// challenge was not extracted, or its proof does not open it against `comm_r_last`.
+    pub fn vanilla_proof(&self, sector_id: SectorId, challenges: &[u64]) -> Result<Proof<P>> {
+        let inclusion_proofs = challenges
+            .iter()
+            .map(|&challenge| {
+                let proof = self.proofs.get(&challenge).with_context(|| {
+                    format!(
+                        "sparse replica of sector {} holds no proof for challenge {}",
+                        sector_id, challenge
+                    )
+                })?;
+                ensure!(
+                    proof.validate(challenge as usize) && proof.root() == self.comm_r_last,
+                    "stored vanilla proof for sector {} is invalid at challenge {}",
+                    sector_id,
+                    challenge
+                );
+
+                Ok(proof.clone())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Proof {
+            sectors: vec![SectorProof {
+                inclusion_proofs,
+                comm_c: CommC(self.comm_c),
+                comm_r_last: CommRLast(self.comm_r_last),
+            }],
+        })
+    }
+}
+
+/// Verifies a batch of proofs, each `partition_proofs[i]` against `pub_inputs[i]`, and returns the
+/// indices of the invalid ones. A proof which cannot be verified at all, e.g. because it is
+/// malformed, counts as invalid. With `early_exit` verification stops at the first invalid proof,
+/// so at most one index is returned; otherwise every proof is verified, for diagnostics.
+pub fn verify_batch<'a, Tree: 'a + MerkleTreeTrait>(
+    pub_params: &PublicParams,
+    pub_inputs: &[PublicInputs<<Tree::Hasher as Hasher>::Domain>],
+    partition_proofs: &[Vec<Proof<Tree::Proof>>],
+    early_exit: bool,
+) -> Result<Vec<usize>> {
+    ensure!(
+        pub_inputs.len() == partition_proofs.len(),
+        "expected {} proofs, got {}",
+        pub_inputs.len(),
+        partition_proofs.len()
+    );
+
+    let mut invalid = Vec::new();
+    for (i, (pub_inputs, proofs)) in pub_inputs.iter().zip(partition_proofs).enumerate() {
+        let is_valid =
+            FallbackPoSt::<'a, Tree>::verify_all_partitions(pub_params, pub_inputs, proofs)
+                .unwrap_or_else(|err| {
+                    error!("could not verify proof {} of batch: {:#}", i, err);
+                    false
+                });
+        if !is_valid {
+            invalid.push(i);
+            if early_exit {
+                break;
+            }
+        }
+    }
+
+    Ok(invalid)
+}
+
+/// Checks that `partitions`, the per-partition public inputs of a window PoSt over `sectors`,
+/// challenge every sector in exactly one partition, split the way `verify_all_partitions` splits
+/// them: partition `k` holds the `k`-th chunk of `pub_params.sector_count` sectors. This catches a
+/// prover which skips, repeats or reorders a partition.
+pub fn verify_partition_cover<T: Domain>(
+    pub_params: &PublicParams,
+    sectors: &[PublicSector<T>],
+    partitions: &[PublicInputs<T>],
+) -> Result<()> {
+    ensure!(pub_params.sector_count > 0, Error::NoSectors);
+    let chunks = sectors.chunks(pub_params.sector_count);
+    ensure!(
+        partitions.len() == chunks.len(),
+        "{} sectors need {} partitions of {}, got {}",
+        sectors.len(),
+        chunks.len(),
+        pub_params.sector_count,
+        partitions.len()
+    );
+
+    for (k, (partition, chunk)) in partitions.iter().zip(chunks).enumerate() {
+        verify_partition_sectors(partition, k, chunk)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that `partition` claims index `k` and challenges exactly the sectors of `chunk`, in order.
+fn verify_partition_sectors<T: Domain>(
+    partition: &PublicInputs<T>,
+    k: usize,
+    chunk: &[PublicSector<T>],
+) -> Result<()> {
+    ensure!(
+        partition.k == Some(k),
+        "partition {} claims index {:?}",
+        k,
+        partition.k
+    );
+    ensure!(
+        partition.sectors.len() == chunk.len()
+            && partition
+                .sectors
+                .iter()
+                .zip(chunk)
+                .all(|(a, b)| a.id == b.id && a.comm_r == b.comm_r),
+        "partition {} does not challenge sectors {:?}",
+        k,
+        chunk.iter().map(|sector| sector.id).collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
+
+/// Checks a submitted window PoSt partition against chain state: `deadline_sectors` are the
+/// sectors assigned to the deadline, `randomness` its challenge randomness and `prover_id` the
+/// miner expected to prove it. The submission must claim partition `k` and that partition's chunk
+/// of `deadline_sectors`, under the same randomness and prover id, so a submission made with stale
+/// randomness or for another miner is rejected.
+pub fn validate_submission<T: Domain>(
+    pub_params: &PublicParams,
+    pub_inputs: &PublicInputs<T>,
+    deadline_sectors: &[PublicSector<T>],
+    randomness: T,
+    prover_id: T,
+    k: usize,
+) -> Result<()> {
+    ensure!(pub_params.sector_count > 0, Error::NoSectors);
+    let chunk = deadline_sectors
+        .chunks(pub_params.sector_count)
+        .nth(k)
+        .with_context(|| format!("{} sectors have no partition {}", deadline_sectors.len(), k))?;
+    verify_partition_sectors(pub_inputs, k, chunk)?;
+
+    ensure!(
+        pub_inputs.randomness == randomness,
+        "submission is challenged by stale randomness"
+    );
+    ensure!(
+        pub_inputs.prover_id == prover_id,
+        "submission is for another prover"
+    );
+
+    Ok(())
+}
+
+/// What proving a set of challenges reads, as returned by `required_replica_ranges`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaRanges {
+    /// Sorted, disjoint byte ranges of the replica.
+    pub ranges: Vec<Range<usize>>,
+    /// For each challenge, the index within its tree row of each node on its inclusion path, from
+    /// the challenged leaf up to a child of the root.
+    pub path_nodes: Vec<Vec<usize>>,
+}
+
+/// Returns the replica byte ranges and tree nodes read to prove `challenges` against a sector of
+/// `sector_nodes` nodes.
+///
+/// PoSt proves from level cache trees, which store neither the leaves nor the `rows_to_discard`
+/// rows above them. `gen_cached_proof` rebuilds those rows from the whole segment of
+/// `arity^(rows_to_discard + 1)` replica leaves below the challenged leaf's lowest cached
+/// ancestor, so each range spans whole segments rather than single arity groups. `rows_to_discard`
+/// is derived as in `vanilla_proof`.
+pub fn required_replica_ranges<Tree: MerkleTreeTrait>(
+    sector_nodes: usize,
+    challenges: &[u64],
+) -> Result<ReplicaRanges> {
+    let base_arity = Tree::Arity::to_usize();
+    let base_tree_leafs = sector_nodes / get_base_tree_count::<Tree>();
+    let rows_to_discard = default_rows_to_discard(sector_nodes, base_arity);
+    let segment_width = base_arity
+        .pow(rows_to_discard as u32 + 1)
+        .min(base_tree_leafs);
+    let upper_arities = [
+        Tree::SubTreeArity::to_usize(),
+        Tree::TopTreeArity::to_usize(),
+    ];
+
+    let mut segments = BTreeSet::new();
+    let mut path_nodes = Vec::with_capacity(challenges.len());
+    for &challenge in challenges {
+        let challenge = challenge as usize;
+        let coords = challenge_coords::<Tree>(challenge, sector_nodes)?;
+        let base_levels = coords.len() - upper_arities.iter().filter(|&&a| a > 0).count();
+
+        let mut node = challenge;
+        let nodes = iter::repeat(base_arity)
+            .take(base_levels)
+            .chain(upper_arities.iter().copied().filter(|&a| a > 0))
+            .map(|arity| {
+                let row_index = node;
+                node /= arity;
+                row_index
+            })
+            .collect();
+
+        segments.insert(challenge / segment_width);
+        path_nodes.push(nodes);
+    }
+
+    let segment_size = segment_width * NODE_SIZE;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for segment in segments {
+        let start = segment * segment_size;
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end += segment_size,
+            _ => ranges.push(start..start + segment_size),
+        }
+    }
+
+    Ok(ReplicaRanges { ranges, path_nodes })
+}
+
+/// Checks that each inclusion proof in `sector_proof` verifies and claims the leaf stored in
+/// `replica` at its challenged node, so a replica and tree which have diverged (e.g. a stale tree on disk) are
+/// caught before proving.
+pub fn validate_replica_leaves<P: MerkleProofTrait, S: LeafSource + ?Sized>(
+    sector_proof: &SectorProof<P>,
+    challenges: &[u64],
+    replica: &S,
+) -> Result<()> {
+    validate_replica_leaves_with(sector_proof, challenges, replica, &IdentityLeafTransform)
+}
+
+/// Like `validate_replica_leaves`, for a replica whose leaves are stored encoded with `transform`.
+pub fn validate_replica_leaves_with<P: MerkleProofTrait, S: LeafSource + ?Sized>(
+    sector_proof: &SectorProof<P>,
+    challenges: &[u64],
+    replica: &S,
+    transform: &dyn LeafTransform,
+) -> Result<()> {
+    ensure!(
+        sector_proof.inclusion_proofs.len() == challenges.len(),
+        "expected {} inclusion proofs, got {}",
+        challenges.len(),
+        sector_proof.inclusion_proofs.len()
+    );
+
+    for (proof, &challenge) in sector_proof.inclusion_proofs.iter().zip(challenges) {
+        let leaf: <P::Hasher as Hasher>::Domain =
+            replica.read_fr_with(challenge as usize, transform)?.into();
+        ensure!(
+            proof.validate_data(leaf),
+            "replica leaf {} does not match its inclusion proof",
+            challenge
+        );
+    }
+
+    Ok(())
+}
+
 impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree> {
     type PublicParams = PublicParams;
     type SetupParams = SetupParams;
@@ -422,9 +1473,9 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree>
                                         if proof.validate(challenged_leaf as usize)
                                             && proof.root() == priv_sector.comm_r_last
                                             && pub_sector.comm_r
-                                                == <Tree::Hasher as Hasher>::Function::hash2(
-                                                    &priv_sector.comm_c,
-                                                    &priv_sector.comm_r_last,
+                                                == comm_r_from_parts::<Tree::Hasher>(
+                                                    CommC(priv_sector.comm_c),
+                                                    CommRLast(priv_sector.comm_r_last),
                                                 )
                                         {
                                             inclusion_proofs.push(proof);
@@ -453,8 +1504,8 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree>
                     (
                         SectorProof {
                             inclusion_proofs,
-                            comm_c: priv_sector.comm_c,
-                            comm_r_last: priv_sector.comm_r_last,
+                            comm_c: CommC(priv_sector.comm_c),
+                            comm_r_last: CommRLast(priv_sector.comm_r_last),
                         },
                         faults,
                     )
@@ -604,11 +1655,10 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree>
                 // comm_r_last is the root of the proof
                 let comm_r_last = inclusion_proofs[0].root();
 
-                if AsRef::<[u8]>::as_ref(&<Tree::Hasher as Hasher>::Function::hash2(
-                    &comm_c,
-                    &comm_r_last,
-                )) != AsRef::<[u8]>::as_ref(comm_r)
-                {
+                if !bool::from(ct_eq(
+                    &comm_r_from_parts::<Tree::Hasher>(comm_c, CommRLast(comm_r_last)),
+                    comm_r,
+                )) {
                     error!("hash(comm_c || comm_r_last) != comm_r: {:?}", sector_id);
                     return Ok(false);
                 }
@@ -639,7 +1689,7 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree>
                             );
 
                         // validate all comm_r_lasts match
-                        if inclusion_proof.root() != comm_r_last {
+                        if !bool::from(ct_eq(&inclusion_proof.root(), &comm_r_last)) {
                             error!("inclusion proof root != comm_r_last: {:?}", sector_id);
                             return Ok(false);
                         }
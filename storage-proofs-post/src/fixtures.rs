@@ -0,0 +1,121 @@
+//! Reproducible PoSt fixtures, for downstream crates writing their own PoSt tests.
+
+use std::path::PathBuf;
+
+use filecoin_hashers::{Domain, HashFunction, Hasher};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    merkle::{generate_tree, MerkleTreeTrait, ResTree},
+    util::NODE_SIZE,
+};
+
+use crate::fallback::{
+    comm_r_from_parts, CommC, CommRLast, PrivateSector, PublicInputs, PublicParams, PublicSector,
+};
+
+/// A replicated sector and the commitments a prover holds for it.
+#[derive(Debug)]
+pub struct FixtureSector<Tree: MerkleTreeTrait> {
+    /// The raw replica bytes.
+    pub replica: Vec<u8>,
+    pub tree: ResTree<Tree>,
+    pub comm_c: <Tree::Hasher as Hasher>::Domain,
+}
+
+/// Everything needed to prove and verify a winning PoSt over freshly generated sectors.
+#[derive(Debug)]
+pub struct WinningFixture<Tree: MerkleTreeTrait> {
+    pub pub_inputs: PublicInputs<<Tree::Hasher as Hasher>::Domain>,
+    pub sectors: Vec<FixtureSector<Tree>>,
+}
+
+impl<Tree: MerkleTreeTrait> WinningFixture<Tree> {
+    /// The private sectors backing `pub_inputs`, in the same order, for building `PrivateInputs`.
+    pub fn priv_sectors(&self) -> Vec<PrivateSector<'_, Tree>> {
+        self.sectors
+            .iter()
+            .map(|sector| PrivateSector::from_tree(&sector.tree, sector.comm_c))
+            .collect()
+    }
+}
+
+/// How a fixture picks each sector's `comm_c`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommCMode {
+    /// Drawn from the fixture's rng, unrelated to the sector.
+    Random,
+    /// `H(comm_r_last)`, so that `comm_r` follows from the replica tree alone, as it does for a
+    /// real sector whose `comm_c` and `comm_r_last` come from the same sealing.
+    DerivedFromRoot,
+}
+
+impl Default for CommCMode {
+    fn default() -> Self {
+        CommCMode::Random
+    }
+}
+
+/// Generates `pub_params.sector_count` sectors of `pub_params.sector_size` bytes, deterministically
+/// from `seed`, and the winning PoSt inputs over them. `temp_path` is passed on to `generate_tree`
+/// and must be set for trees whose store lives on disk, such as `LCTree`.
+pub fn winning_fixture<Tree: MerkleTreeTrait>(
+    seed: [u8; 16],
+    pub_params: &PublicParams,
+    temp_path: Option<PathBuf>,
+) -> WinningFixture<Tree>
+where
+    Tree::Store: 'static,
+{
+    winning_fixture_with::<Tree>(seed, pub_params, temp_path, CommCMode::default())
+}
+
+/// Like `winning_fixture`, but picks each sector's `comm_c` according to `comm_c_mode`.
+pub fn winning_fixture_with<Tree: MerkleTreeTrait>(
+    seed: [u8; 16],
+    pub_params: &PublicParams,
+    temp_path: Option<PathBuf>,
+    comm_c_mode: CommCMode,
+) -> WinningFixture<Tree>
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(seed);
+    let nodes = pub_params.sector_size as usize / NODE_SIZE;
+
+    let randomness = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let prover_id = <Tree::Hasher as Hasher>::Domain::random(rng);
+
+    let mut sectors = Vec::with_capacity(pub_params.sector_count);
+    let mut pub_sectors = Vec::with_capacity(pub_params.sector_count);
+    for id in 0..pub_params.sector_count as u64 {
+        let (replica, tree) = generate_tree::<Tree, _>(rng, nodes, temp_path.clone());
+        let comm_c = match comm_c_mode {
+            CommCMode::Random => <Tree::Hasher as Hasher>::Domain::random(rng),
+            CommCMode::DerivedFromRoot => {
+                <Tree::Hasher as Hasher>::Function::hash(tree.root().as_ref())
+            }
+        };
+        let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(tree.root()));
+
+        pub_sectors.push(PublicSector {
+            id: id.into(),
+            comm_r,
+        });
+        sectors.push(FixtureSector {
+            replica,
+            tree,
+            comm_c,
+        });
+    }
+
+    WinningFixture {
+        pub_inputs: PublicInputs {
+            randomness,
+            prover_id,
+            sectors: pub_sectors,
+            k: None,
+        },
+        sectors,
+    }
+}
@@ -3,4 +3,6 @@
 
 pub mod election;
 pub mod fallback;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod rational;
@@ -5,6 +5,7 @@ use anyhow::{bail, ensure, Context};
 use blake2b_simd::blake2b;
 use byteorder::{ByteOrder, LittleEndian};
 use filecoin_hashers::{Domain, HashFunction, Hasher};
+use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use storage_proofs_core::{
     error::{Error, Result},
@@ -148,9 +149,11 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for RationalPoSt<'a, Tree>
         );
         let challenges = &pub_inputs.challenges;
 
+        // Challenges are independent, so their proofs are generated in parallel; `collect` keeps
+        // them in challenge order.
         let proofs = challenges
-            .iter()
-            .zip(priv_inputs.comm_r_lasts.iter())
+            .par_iter()
+            .zip(priv_inputs.comm_r_lasts.par_iter())
             .map(|(challenge, comm_r_last)| {
                 let challenged_leaf = challenge.leaf;
 
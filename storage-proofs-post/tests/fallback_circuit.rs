@@ -4,22 +4,32 @@ use bellperson::{
 };
 use blstrs::Scalar as Fr;
 use ff::Field;
-use filecoin_hashers::{poseidon::PoseidonHasher, Domain, HashFunction, Hasher};
+use filecoin_hashers::{poseidon::PoseidonHasher, Domain, Hasher};
 use generic_array::typenum::{U0, U2, U4, U8};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
 use storage_proofs_core::{
     api_version::ApiVersion,
+    commitment,
     compound_proof::CompoundProof,
-    error::Result,
-    merkle::{generate_tree, get_base_tree_count, LCTree, MerkleTreeTrait, OctMerkleTree},
+    error::{Error, Result},
+    merkle::{
+        generate_tree, get_base_tree_count, LCTree, MerkleProofTrait, MerkleTreeTrait,
+        OctMerkleTree,
+    },
     proof::ProofScheme,
     util::NODE_SIZE,
     TEST_SEED,
 };
-use storage_proofs_post::fallback::{
-    self, FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound, PrivateSector, PublicSector,
-    Sector,
+use storage_proofs_post::{
+    fallback::{
+        self, comm_r_from_parts, path_to_circuit, ColumnRootsSector, ColumnRootsTree, CommC,
+        CommRLast, Entropy, EntropyCircuit, FallbackPoSt, FallbackPoStCircuit,
+        FallbackPoStCompound, PrivateSector, PublicSector, Sector, WeightedChallenges,
+        WeightedSector,
+    },
+    fixtures,
 };
 use tempfile::tempdir;
 
@@ -98,7 +108,7 @@ fn test_fallback_post<Tree: 'static + MerkleTreeTrait>(
             comm_r_last,
         });
 
-        let comm_r = <Tree::Hasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
         pub_sectors.push(PublicSector {
             id: (i as u64).into(),
             comm_r,
@@ -129,6 +139,13 @@ fn test_fallback_post<Tree: 'static + MerkleTreeTrait>(
         .expect("verification failed");
     assert!(is_valid);
 
+    assert_eq!(
+        FallbackPoStCircuit::<Tree>::estimated_constraints(&pub_params, sector_count)
+            .expect("estimated_constraints failure"),
+        expected_constraints,
+        "wrong constraint estimate"
+    );
+
     // actual circuit test
 
     for (j, proof) in proofs.iter().enumerate() {
@@ -201,6 +218,714 @@ fn test_fallback_post<Tree: 'static + MerkleTreeTrait>(
     }
 }
 
+#[test]
+fn test_fallback_post_circuit_poseidon_entropy_base_8() {
+    test_fallback_post_entropy::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+fn test_fallback_post_entropy<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+    let sector_size = leaves * NODE_SIZE;
+    let randomness = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let prover_id = <Tree::Hasher as Hasher>::Domain::random(rng);
+
+    let pub_params = fallback::PublicParams {
+        sector_size: sector_size as u64,
+        challenge_count: 5,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let temp_path = temp_dir.path();
+
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_path.to_path_buf()));
+    let comm_c = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let pub_sectors = vec![PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    }];
+
+    let pub_inputs = fallback::PublicInputs {
+        randomness,
+        prover_id,
+        sectors: pub_sectors.clone(),
+        k: None,
+    };
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let proofs =
+        FallbackPoSt::<Tree>::prove_all_partitions(&pub_params, &pub_inputs, &priv_inputs, 1)
+            .expect("proving failed");
+    let generated_inputs =
+        FallbackPoStCompound::<Tree>::generate_public_inputs(&pub_inputs, &pub_params, Some(0))
+            .expect("generate_public_inputs failure");
+
+    let entropy = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let commitment = Entropy::commitment::<Tree::Hasher>(entropy);
+
+    for bound in [false, true] {
+        let circuit_sectors = vec![Sector::circuit(&pub_sectors[0], &proofs[0].sectors[0])
+            .expect("circuit sector failure")];
+
+        let circuit = FallbackPoStCircuit::<Tree> {
+            sectors: circuit_sectors,
+            prover_id: Some(prover_id.into()),
+        };
+
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let synthesized = if bound {
+            EntropyCircuit {
+                circuit,
+                entropy: Entropy {
+                    preimage: Some(entropy.into()),
+                },
+            }
+            .synthesize(&mut cs)
+        } else {
+            circuit.synthesize(&mut cs)
+        };
+        synthesized.expect("failed to synthesize circuit");
+        assert!(cs.is_satisfied(), "constraints not satisfied");
+
+        let mut expected_inputs = generated_inputs.clone();
+        if bound {
+            expected_inputs.push(commitment.into());
+        }
+        assert_eq!(cs.num_inputs(), expected_inputs.len() + 1);
+        assert!(cs.verify(&expected_inputs), "verification failed");
+
+        if bound {
+            // A different entropy commitment must not verify.
+            let mut wrong_inputs = generated_inputs.clone();
+            wrong_inputs.push(comm_c.into());
+            assert!(!cs.verify(&wrong_inputs));
+        }
+    }
+}
+
+#[test]
+fn test_fallback_post_estimated_constraints() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: 5,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+    let comm_c = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let challenges = fallback::generate_leaf_challenges(
+        &pub_params,
+        <Tree::Hasher as Hasher>::Domain::random(rng),
+        0,
+        pub_params.challenge_count,
+    );
+    let proof = fallback::vanilla_proof(0u64.into(), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    let pub_sector = PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    };
+    let sector =
+        Sector::<Tree>::circuit(&pub_sector, &proof.sectors[0]).expect("sector circuit failure");
+
+    // `TestConstraintSystem` evaluates every assignment, which a blank sector does not have, so
+    // the circuits are built from a proven sector instead.
+    let num_constraints: Vec<usize> = [1, 2]
+        .iter()
+        .map(|&sector_count| {
+            let circuit = FallbackPoStCircuit::<Tree> {
+                prover_id: None,
+                sectors: vec![sector.clone(); sector_count],
+            };
+
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            circuit
+                .synthesize(&mut cs)
+                .expect("failed to synthesize circuit");
+            assert!(cs.is_satisfied(), "constraints not satisfied");
+
+            assert_eq!(
+                FallbackPoStCircuit::<Tree>::estimated_constraints(&pub_params, sector_count)
+                    .expect("estimated_constraints failure"),
+                cs.num_constraints(),
+                "wrong constraint estimate for {} sectors",
+                sector_count
+            );
+
+            cs.num_constraints()
+        })
+        .collect();
+    assert_eq!(num_constraints[1], 2 * num_constraints[0]);
+}
+
+#[test]
+fn test_fallback_post_circuit_poseidon_weighted_base_8() {
+    test_fallback_post_weighted::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+fn test_fallback_post_weighted<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let temp_path = temp_dir.path();
+
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_path.to_path_buf()));
+    let comm_c = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    // Challenge 3 is drawn twice and collapses to a single proof of weight 2.
+    let collapsed = WeightedChallenges::new(&[3, 10, 3]);
+    assert_eq!(collapsed.challenges(), vec![3, 10]);
+    assert_eq!(collapsed.weights(), vec![2, 1]);
+    assert_eq!(collapsed.total_weight(), 3);
+
+    // Drawing one more challenge than there are leaves guarantees a duplicate.
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: leaves + 1,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let randomness = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let weighted = WeightedChallenges::derive(&pub_params, randomness, 0);
+    assert_eq!(weighted.total_weight(), pub_params.challenge_count as u64);
+    assert!(weighted.len() < pub_params.challenge_count);
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = fallback::vanilla_proof(0u64.into(), &priv_inputs, &weighted.challenges())
+        .expect("vanilla proof failure");
+
+    let pub_sector = PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    };
+
+    // Proofs must line up with the unique challenges they are weighted against.
+    let mut reordered = weighted.challenges();
+    reordered.reverse();
+    assert!(WeightedSector::<Tree>::circuit(
+        &pub_sector,
+        &proof.sectors[0],
+        &WeightedChallenges::new(&reordered)
+    )
+    .is_err());
+
+    let circuit = WeightedSector::<Tree>::circuit(&pub_sector, &proof.sectors[0], &weighted)
+        .expect("weighted sector failure");
+
+    let mut cs = TestConstraintSystem::<Fr>::new();
+    (&circuit)
+        .synthesize(&mut cs)
+        .expect("failed to synthesize circuit");
+    assert!(cs.is_satisfied(), "constraints not satisfied");
+
+    let inputs =
+        WeightedSector::<Tree>::generate_public_inputs(&pub_params, randomness, &pub_sector)
+            .expect("generate_public_inputs failure");
+    assert_eq!(cs.num_inputs(), inputs.len() + 1);
+    assert!(cs.verify(&inputs), "failed to verify inputs");
+
+    // Dropping a duplicate's weight must not verify.
+    let duplicate = weighted
+        .weights()
+        .iter()
+        .position(|&w| w > 1)
+        .expect("no duplicate challenge");
+    let mut unweighted = inputs;
+    let index = unweighted.len() - weighted.len() + duplicate;
+    unweighted[index] = Fr::from(1u64);
+    assert!(!cs.verify(&unweighted), "verified with wrong weight");
+
+    // Public inputs derived under other randomness do not verify this proof.
+    let other = WeightedSector::<Tree>::generate_public_inputs(
+        &pub_params,
+        <Tree::Hasher as Hasher>::Domain::random(rng),
+        &pub_sector,
+    )
+    .expect("generate_public_inputs failure");
+    assert!(!cs.verify(&other), "verified under other randomness");
+}
+
+#[cfg(feature = "challenge-selector")]
+#[test]
+fn test_fallback_post_circuit_poseidon_selected_base_8() {
+    test_fallback_post_selected::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+#[cfg(feature = "challenge-selector")]
+fn test_fallback_post_selected<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    use storage_proofs_post::fallback::SelectedSector;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+    let comm_c = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let challenges: Vec<u64> = vec![1, 5, 9, 17, 23, 42, 50, 63];
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = fallback::vanilla_proof(0u64.into(), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    let pub_sector = PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    };
+    let mut sector =
+        Sector::<Tree>::circuit(&pub_sector, &proof.sectors[0]).expect("circuit sector failure");
+
+    // Only the first three challenges are proven; the others' leafs are not in the tree.
+    for leaf in sector.leafs.iter_mut().skip(3) {
+        *leaf = Some(Fr::random(&mut *rng));
+    }
+    let active: Vec<bool> = (0..challenges.len()).map(|i| i < 3).collect();
+
+    assert!(SelectedSector::new(sector.clone(), &active[..3]).is_err());
+    let circuit = SelectedSector::new(sector.clone(), &active).expect("selected sector failure");
+
+    let mut cs = TestConstraintSystem::<Fr>::new();
+    (&circuit)
+        .synthesize(&mut cs)
+        .expect("failed to synthesize circuit");
+    assert!(cs.is_satisfied(), "constraints not satisfied");
+
+    let inputs =
+        SelectedSector::<Tree>::generate_public_inputs(comm_r, &challenges, &active, leaves)
+            .expect("generate_public_inputs failure");
+    assert_eq!(cs.num_inputs(), inputs.len() + 1);
+    assert!(cs.verify(&inputs), "failed to verify inputs");
+
+    // Claiming a skipped challenge as proven must not verify.
+    let mut overclaimed = inputs;
+    let len = overclaimed.len();
+    overclaimed[len - 1] = Fr::one();
+    assert!(!cs.verify(&overclaimed), "verified a skipped challenge");
+
+    // Nor may a skipped challenge be marked active.
+    let all_active = vec![true; challenges.len()];
+    let circuit = SelectedSector::new(sector, &all_active).expect("selected sector failure");
+    let mut cs = TestConstraintSystem::<Fr>::new();
+    (&circuit)
+        .synthesize(&mut cs)
+        .expect("failed to synthesize circuit");
+    assert!(
+        !cs.is_satisfied(),
+        "skipped challenges satisfied the circuit"
+    );
+}
+
+#[test]
+fn test_fallback_post_circuit_poseidon_column_roots_base_8() {
+    test_fallback_post_column_roots::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+fn test_fallback_post_column_roots<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    // Four public column roots, hashed into comm_c by a single arity 4 node.
+    let column_roots: Vec<_> = (0..4)
+        .map(|_| <Tree::Hasher as Hasher>::Domain::random(rng))
+        .collect();
+    let comm_c =
+        commitment::comm_c_from_column_roots::<ColumnRootsTree<Tree::Hasher, U4>>(&column_roots)
+            .expect("comm_c failure");
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = fallback::vanilla_proof(0u64.into(), &priv_inputs, &[3, 10])
+        .expect("vanilla proof failure");
+
+    let pub_sector = PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    };
+
+    // Roots which do not derive comm_c are rejected up front.
+    let mut wrong_roots = column_roots.clone();
+    wrong_roots.swap(0, 1);
+    assert!(
+        ColumnRootsSector::<Tree, U4>::circuit(&pub_sector, &proof.sectors[0], &wrong_roots)
+            .is_err()
+    );
+
+    let circuit =
+        ColumnRootsSector::<Tree, U4>::circuit(&pub_sector, &proof.sectors[0], &column_roots)
+            .expect("column roots sector failure");
+
+    let mut cs = TestConstraintSystem::<Fr>::new();
+    (&circuit)
+        .synthesize(&mut cs)
+        .expect("failed to synthesize circuit");
+    assert!(cs.is_satisfied(), "constraints not satisfied");
+
+    let mut inputs = vec![comm_r.into()];
+    inputs.extend(column_roots.iter().map(|root| Fr::from(*root)));
+    assert_eq!(cs.num_inputs(), inputs.len() + 1);
+    assert!(cs.verify(&inputs), "failed to verify inputs");
+
+    inputs.swap(1, 2);
+    assert!(!cs.verify(&inputs), "verified with swapped column roots");
+
+    // The plain sector circuit, without column roots, is unchanged.
+    let mut plain_cs = TestConstraintSystem::<Fr>::new();
+    (&circuit.sector)
+        .synthesize(&mut plain_cs)
+        .expect("failed to synthesize circuit");
+    assert!(plain_cs.is_satisfied(), "constraints not satisfied");
+    assert!(plain_cs.verify(&[comm_r.into()]), "failed to verify inputs");
+}
+
+#[test]
+fn test_fallback_post_sector_zeroize() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+
+    let mut sector = Sector::<OctMerkleTree<PoseidonHasher>> {
+        comm_r: Some(Fr::random(&mut rng)),
+        comm_c: Some(Fr::random(&mut rng)),
+        comm_r_last: Some(Fr::random(&mut rng)),
+        leafs: (0..4).map(|_| Some(Fr::random(&mut rng))).collect(),
+        paths: Vec::new(),
+        id: Some(Fr::one()),
+    };
+    let comm_r = sector.comm_r;
+    let leafs_ptr = sector.leafs.as_ptr();
+
+    sector.zeroize();
+
+    // The leafs' backing memory is overwritten in place, not reallocated.
+    assert_eq!(sector.leafs.as_ptr(), leafs_ptr);
+    for i in 0..sector.leafs.len() {
+        let leaf = unsafe { std::ptr::read_volatile(leafs_ptr.add(i)) };
+        assert_eq!(leaf, Some(Fr::zero()));
+    }
+    assert_eq!(sector.comm_c, Some(Fr::zero()));
+    assert_eq!(sector.comm_r_last, Some(Fr::zero()));
+
+    // Public values are left alone.
+    assert_eq!(sector.comm_r, comm_r);
+    assert_eq!(sector.id, Some(Fr::one()));
+}
+
+#[test]
+fn test_fallback_post_sector_from_triples_base_8() {
+    test_fallback_post_sector_from_triples::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+#[test]
+fn test_fallback_post_sector_from_triples_top_8_4_2() {
+    test_fallback_post_sector_from_triples::<LCTree<PoseidonHasher, U8, U4, U2>>();
+}
+
+fn test_fallback_post_sector_from_triples<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let leaves = 64 * get_base_tree_count::<Tree>();
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: 3,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+    let comm_c = <Tree::Hasher as Hasher>::Domain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let challenges = [3, 27, leaves as u64 - 1];
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = fallback::vanilla_proof(0u64.into(), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let pub_sector = PublicSector {
+        id: 0u64.into(),
+        comm_r,
+    };
+
+    // Assemble the triples as a prior stage would hand them over, with no tree attached.
+    let triples: Vec<_> = challenges
+        .iter()
+        .zip(proof.sectors[0].inclusion_proofs())
+        .map(|(&challenge, proof)| {
+            let siblings = proof
+                .path()
+                .into_iter()
+                .map(|(siblings, _)| siblings.into_iter().map(|s| Some(s.into())).collect())
+                .collect();
+            (challenge, proof.leaf().into(), siblings)
+        })
+        .collect();
+
+    let from_tree =
+        Sector::<Tree>::circuit(&pub_sector, &proof.sectors[0]).expect("sector failure");
+    let from_triples = Sector::<Tree>::from_triples(
+        &pub_params,
+        &pub_sector,
+        comm_c,
+        comm_r_last,
+        triples.clone(),
+    )
+    .expect("from_triples failure");
+
+    let mut cs_tree = TestConstraintSystem::<Fr>::new();
+    (&from_tree)
+        .synthesize(&mut cs_tree)
+        .expect("failed to synthesize circuit");
+    let mut cs_triples = TestConstraintSystem::<Fr>::new();
+    (&from_triples)
+        .synthesize(&mut cs_triples)
+        .expect("failed to synthesize circuit");
+
+    assert!(cs_triples.is_satisfied(), "constraints not satisfied");
+    assert!(
+        cs_triples.verify(&[comm_r.into()]),
+        "failed to verify inputs"
+    );
+    assert_eq!(cs_triples.num_constraints(), cs_tree.num_constraints());
+    assert_eq!(cs_triples.hash(), cs_tree.hash());
+    assert_eq!(from_triples.leafs, from_tree.leafs);
+
+    // Shapes are checked against the tree: a missing challenge, a missing level and a missing
+    // sibling are all rejected.
+    let mut short = triples.clone();
+    short.pop();
+    assert!(
+        Sector::<Tree>::from_triples(&pub_params, &pub_sector, comm_c, comm_r_last, short).is_err()
+    );
+
+    let mut shallow = triples.clone();
+    shallow[0].2.pop();
+    assert!(
+        Sector::<Tree>::from_triples(&pub_params, &pub_sector, comm_c, comm_r_last, shallow)
+            .is_err()
+    );
+
+    let mut narrow = triples;
+    narrow[1].2[0].pop();
+    let err = Sector::<Tree>::from_triples(&pub_params, &pub_sector, comm_c, comm_r_last, narrow)
+        .expect_err("narrow path accepted");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::ArityMismatch(0, 7, 6))
+    ));
+}
+
+/// Wraps a valid proof but drops one sibling from the first level of its path.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct TruncatedProof<P>(P);
+
+impl<P: MerkleProofTrait> MerkleProofTrait for TruncatedProof<P> {
+    type Hasher = P::Hasher;
+    type Arity = P::Arity;
+    type SubTreeArity = P::SubTreeArity;
+    type TopTreeArity = P::TopTreeArity;
+
+    fn try_from_proof(
+        p: merkletree::proof::Proof<<Self::Hasher as Hasher>::Domain, Self::Arity>,
+    ) -> Result<Self> {
+        P::try_from_proof(p).map(TruncatedProof)
+    }
+
+    fn verify(&self) -> bool {
+        self.0.verify()
+    }
+
+    fn leaf(&self) -> <Self::Hasher as Hasher>::Domain {
+        self.0.leaf()
+    }
+
+    fn root(&self) -> <Self::Hasher as Hasher>::Domain {
+        self.0.root()
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn path(&self) -> Vec<(Vec<<Self::Hasher as Hasher>::Domain>, usize)> {
+        let mut path = self.0.path();
+        path[0].0.pop();
+        path
+    }
+}
+
+#[test]
+fn test_path_to_circuit_arity_mismatch() {
+    let mut rng = XorShiftRng::from_seed(TEST_SEED);
+    let (_data, tree) = generate_tree::<OctMerkleTree<PoseidonHasher>, _>(&mut rng, 64, None);
+    let proof = tree.gen_proof(3).expect("gen_proof failure");
+
+    let path = path_to_circuit(&proof).expect("valid path rejected");
+    assert_eq!(path.len(), 2);
+    assert!(path.iter().all(|(siblings, _)| siblings.len() == 7));
+
+    let err = path_to_circuit(&TruncatedProof(proof)).expect_err("truncated path accepted");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::ArityMismatch(0, 7, 6))
+    ));
+}
+
+#[test]
+fn test_winning_post_circuit_tampered_base_8() {
+    test_winning_post_circuit_tampered::<LCTree<PoseidonHasher, U8, U0, U0>>();
+}
+
+#[test]
+fn test_winning_post_circuit_tampered_sub_8_4() {
+    test_winning_post_circuit_tampered::<LCTree<PoseidonHasher, U8, U4, U0>>();
+}
+
+#[test]
+fn test_winning_post_circuit_tampered_top_8_4_2() {
+    test_winning_post_circuit_tampered::<LCTree<PoseidonHasher, U8, U4, U2>>();
+}
+
+/// Replaces a single sibling of each path level in turn with a random value, and checks that the
+/// circuit is no longer satisfied, i.e. that every level of the merkle path is constrained.
+fn test_winning_post_circuit_tampered<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let pub_params = fallback::PublicParams {
+        sector_size: (64 * get_base_tree_count::<Tree>() * NODE_SIZE) as u64,
+        challenge_count: 5,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = FallbackPoSt::<Tree>::prove(&pub_params, &fixture.pub_inputs, &priv_inputs)
+        .expect("proving failed");
+    let sector_proof = &proof.sectors[0];
+    let pub_sector = &fixture.pub_inputs.sectors[0];
+
+    let is_satisfied = |sector: Sector<Tree>| {
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        let instance = FallbackPoStCircuit::<Tree> {
+            sectors: vec![sector],
+            prover_id: Some(fixture.pub_inputs.prover_id.into()),
+        };
+        instance
+            .synthesize(&mut cs)
+            .expect("failed to synthesize circuit");
+        cs.is_satisfied()
+    };
+
+    let sector = Sector::circuit(pub_sector, sector_proof).expect("circuit sector failure");
+    assert!(is_satisfied(sector), "untampered proof not satisfied");
+
+    let path = path_to_circuit(&sector_proof.inclusion_proofs()[0]).expect("path failure");
+    for level in 0..path.len() {
+        let mut tampered_path = path.clone();
+        tampered_path[level].0[0] = Some(Fr::random(&mut *rng));
+
+        let mut sector = Sector::circuit(pub_sector, sector_proof).expect("circuit sector failure");
+        sector.paths[0] = tampered_path.into();
+        assert!(
+            !is_satisfied(sector),
+            "tampered sibling at level {} satisfied the circuit",
+            level
+        );
+    }
+}
+
 #[test]
 #[ignore]
 fn test_fallback_post_circuit_poseidon_base_8_bench_cs() {
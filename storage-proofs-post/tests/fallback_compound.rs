@@ -2,7 +2,7 @@ use bellperson::{
     util_cs::{metric_cs::MetricCS, test_cs::TestConstraintSystem},
     Circuit,
 };
-use filecoin_hashers::{poseidon::PoseidonHasher, Domain, HashFunction, Hasher};
+use filecoin_hashers::{poseidon::PoseidonHasher, Domain, Hasher};
 use generic_array::typenum::{U0, U2, U4, U8};
 use pretty_assertions::assert_eq;
 use rand::SeedableRng;
@@ -10,13 +10,15 @@ use rand_xorshift::XorShiftRng;
 use storage_proofs_core::{
     api_version::ApiVersion,
     compound_proof::{self, CompoundProof},
+    error::Error,
     merkle::{generate_tree, get_base_tree_count, LCTree, MerkleTreeTrait},
     util::NODE_SIZE,
     TEST_SEED,
 };
 use storage_proofs_post::fallback::{
-    ChallengeRequirements, FallbackPoStCompound, PrivateInputs, PrivateSector, PublicInputs,
-    PublicSector, SetupParams,
+    self, comm_r_from_parts, ChallengeRequirements, CommC, CommRLast, ComponentPrivateInputs,
+    FallbackPoStCompound, PrivateInputs, PrivateSector, PublicInputs, PublicSector, SectorProof,
+    SetupParams,
 };
 use tempfile::tempdir;
 
@@ -110,7 +112,7 @@ fn fallback_post<Tree: 'static + MerkleTreeTrait>(
             comm_r_last,
         });
 
-        let comm_r = <Tree::Hasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
         pub_sectors.push(PublicSector {
             id: (i as u64).into(),
             comm_r,
@@ -198,3 +200,106 @@ fn fallback_post<Tree: 'static + MerkleTreeTrait>(
 
     assert!(verified);
 }
+
+#[test]
+fn test_fallback_post_compound_circuit_rejects_no_sectors() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+    type TreeDomain = <PoseidonHasher as Hasher>::Domain;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let sector = PublicSector {
+        id: 0u64.into(),
+        comm_r: TreeDomain::random(rng),
+    };
+    let sector_proof = SectorProof {
+        inclusion_proofs: Vec::new(),
+        comm_c: CommC(TreeDomain::random(rng)),
+        comm_r_last: CommRLast(TreeDomain::random(rng)),
+    };
+    let pub_params = |sector_count| fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 1,
+        sector_count,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let mut pub_inputs = |sectors| PublicInputs::<TreeDomain> {
+        randomness: TreeDomain::random(rng),
+        prover_id: TreeDomain::random(rng),
+        sectors,
+        k: None,
+    };
+    let circuit = |pub_inputs: &PublicInputs<TreeDomain>, proof, sector_count| {
+        FallbackPoStCompound::<Tree>::circuit(
+            pub_inputs,
+            ComponentPrivateInputs {},
+            &fallback::Proof { sectors: proof },
+            &pub_params(sector_count),
+            None,
+        )
+    };
+
+    // A zero sector count, e.g. for an unsupported sector size.
+    let err = circuit(&pub_inputs(vec![sector.clone()]), Vec::new(), 0)
+        .err()
+        .expect("zero sector count accepted");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::NoSectors)
+    ));
+
+    // Proofs, but no public sectors to bind them to.
+    let err = circuit(&pub_inputs(Vec::new()), vec![sector_proof.clone()], 1)
+        .err()
+        .expect("empty public sectors accepted");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::NoSectors)
+    ));
+
+    // Proofs disagreeing with the sector count are still rejected.
+    assert!(circuit(&pub_inputs(vec![sector]), vec![sector_proof], 2).is_err());
+}
+
+#[test]
+fn test_pack_public_inputs() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 3,
+        sector_count: 2,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let partitions = 2;
+
+    let pub_inputs: Vec<_> = (0..3)
+        .map(|_| PublicInputs {
+            randomness: <PoseidonHasher as Hasher>::Domain::random(rng),
+            prover_id: <PoseidonHasher as Hasher>::Domain::random(rng),
+            sectors: (0..4u64)
+                .map(|id| PublicSector {
+                    id: id.into(),
+                    comm_r: <PoseidonHasher as Hasher>::Domain::random(rng),
+                })
+                .collect(),
+            k: None,
+        })
+        .collect();
+
+    let packed =
+        FallbackPoStCompound::<Tree>::pack_public_inputs(&pub_params, &pub_inputs, partitions)
+            .expect("pack failure");
+
+    assert_eq!(packed.len(), pub_inputs.len());
+    for (proof_inputs, pub_in) in packed.iter().zip(&pub_inputs) {
+        assert_eq!(proof_inputs.len(), partitions);
+        for (k, partition_inputs) in proof_inputs.iter().enumerate() {
+            assert_eq!(
+                partition_inputs,
+                &FallbackPoStCompound::<Tree>::generate_public_inputs(pub_in, &pub_params, Some(k))
+                    .expect("generate public inputs failure")
+            );
+        }
+    }
+}
@@ -1,17 +1,38 @@
-use filecoin_hashers::{poseidon::PoseidonHasher, Domain, HashFunction, Hasher};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use filecoin_hashers::{
+    poseidon::{PoseidonDomain, PoseidonHasher},
+    Domain, HashFunction, Hasher,
+};
+use fr32::{bytes_into_fr, bytes_into_fr_with, fr_into_bytes_with, Fr32Ary, LeafTransform};
 use generic_array::typenum::{U0, U2, U4, U8};
+use merkletree::{merkle::get_merkle_tree_len, store::StoreConfig};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
 use storage_proofs_core::{
     api_version::ApiVersion,
-    error::Error,
-    merkle::{generate_tree, get_base_tree_count, LCTree, MerkleTreeTrait},
+    error::{Error, Result},
+    leaf_source::{LeafSource, MmapLeafSource},
+    merkle::{
+        generate_tree, get_base_tree_count, ExternalReader, LCStore, LCTree, MerkleProof,
+        MerkleProofTrait, MerkleTreeTrait,
+    },
     proof::ProofScheme,
     sector::SectorId,
-    util::NODE_SIZE,
+    util::{default_rows_to_discard, NODE_SIZE},
     TEST_SEED,
 };
-use storage_proofs_post::fallback::{self, FallbackPoSt, PrivateSector, PublicSector};
+use storage_proofs_post::{
+    fallback::{
+        self, comm_r_from_parts, CommC, CommRLast, FallbackPoSt, PrivateSector, PublicSector,
+    },
+    fixtures,
+};
 use tempfile::tempdir;
 
 #[test]
@@ -153,7 +174,7 @@ fn test_fallback_post<Tree: MerkleTreeTrait>(
             comm_r_last,
         });
 
-        let comm_r = <Tree::Hasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
         pub_sectors.push(PublicSector {
             id: (i as u64).into(),
             comm_r,
@@ -183,6 +204,27 @@ fn test_fallback_post<Tree: MerkleTreeTrait>(
         .expect("verification failed");
 
     assert!(is_valid);
+
+    // Each partition proves exactly the challenges derived for its sectors.
+    for (k, (partition_proof, partition_sectors)) in proof
+        .iter()
+        .zip(pub_inputs.sectors.chunks(sector_count))
+        .enumerate()
+    {
+        let sector_ids: Vec<SectorId> = partition_sectors.iter().map(|sector| sector.id).collect();
+        let challenges =
+            fallback::generate_partition_challenges(&pub_params, randomness, k, &sector_ids);
+        assert_eq!(challenges.len(), sector_ids.len());
+
+        for (sector_proof, sector_challenges) in partition_proof.sectors.iter().zip(&challenges) {
+            assert_eq!(sector_challenges.len(), pub_params.challenge_count);
+            assert!(sector_proof
+                .inclusion_proofs
+                .iter()
+                .zip(sector_challenges)
+                .all(|(proof, &challenge)| proof.validate(challenge as usize)));
+        }
+    }
 }
 
 #[test]
@@ -335,7 +377,7 @@ fn test_invalid_fallback_post<Tree: MerkleTreeTrait>(
             comm_r_last,
         });
 
-        let comm_r = <Tree::Hasher as Hasher>::Function::hash2(&comm_c, &comm_r_last);
+        let comm_r = comm_r_from_parts::<Tree::Hasher>(CommC(comm_c), CommRLast(comm_r_last));
 
         if make_faulty {
             faulty_sectors.push((i as u64).into());
@@ -379,3 +421,1220 @@ fn test_invalid_fallback_post<Tree: MerkleTreeTrait>(
         },
     };
 }
+
+#[test]
+fn test_challenge_set_diff() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let randomness_a = PoseidonDomain::from([1u8; 32]);
+    let randomness_b = PoseidonDomain::from([2u8; 32]);
+
+    let (only_a, only_b, both) =
+        fallback::challenge_set_diff(&pub_params, randomness_a, randomness_b, 7, 10);
+
+    assert_eq!(only_a, vec![1, 2, 4, 8, 10, 23, 52, 63]);
+    assert_eq!(only_b, vec![13, 17, 18, 26, 27, 32, 48, 51]);
+    assert_eq!(both, vec![36, 44]);
+
+    let (only_a, only_b, both) =
+        fallback::challenge_set_diff(&pub_params, randomness_a, randomness_a, 7, 10);
+
+    assert!(only_a.is_empty());
+    assert!(only_b.is_empty());
+    assert_eq!(both.len(), 10);
+}
+
+#[test]
+fn test_challenge_hash_to_field_default() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let randomness = PoseidonDomain::from([1u8; 32]);
+
+    assert_eq!(
+        fallback::ChallengeHashToField::default(),
+        fallback::ChallengeHashToField::Sha256
+    );
+
+    let challenges = fallback::generate_leaf_challenges_with(
+        fallback::ChallengeHashToField::default(),
+        &pub_params,
+        randomness,
+        7,
+        10,
+    );
+    assert_eq!(challenges, vec![44, 10, 1, 36, 63, 8, 2, 52, 4, 23]);
+    assert_eq!(
+        challenges,
+        fallback::generate_leaf_challenges(&pub_params, randomness, 7, 10)
+    );
+
+    let blake2b_challenges = fallback::generate_leaf_challenges_with(
+        fallback::ChallengeHashToField::Blake2b,
+        &pub_params,
+        randomness,
+        7,
+        10,
+    );
+    assert_ne!(challenges, blake2b_challenges);
+}
+
+#[test]
+fn test_validate_replica_leaves() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c: PoseidonDomain::random(rng),
+        comm_r_last: tree.root(),
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let challenges = [1, 17, 42];
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &data[..])
+        .expect("consistent replica rejected");
+
+    // The same check reading only the challenged leaves from a replica file.
+    let replica_path = temp_dir.path().join("replica");
+    std::fs::write(&replica_path, &data).expect("write failure");
+    let source = MmapLeafSource::open(&replica_path).expect("mmap failure");
+    fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &source)
+        .expect("consistent replica file rejected");
+
+    // A replica which diverged from its tree at a challenged node is caught before proving.
+    let mut stale = data.clone();
+    let other = PoseidonDomain::random(rng);
+    stale[17 * NODE_SIZE..18 * NODE_SIZE].copy_from_slice(other.as_ref());
+    assert!(fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &stale[..]).is_err());
+
+    // A challenged node which is not a field element is reported as such.
+    stale[17 * NODE_SIZE..18 * NODE_SIZE].copy_from_slice(&[0xff; NODE_SIZE]);
+    let err = fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &stale[..])
+        .expect_err("non-canonical leaf accepted");
+    assert!(matches!(
+        err.downcast_ref::<fr32::Error>(),
+        Some(fr32::Error::NonCanonical { top_bits_set: true })
+    ));
+}
+
+/// Stores each leaf byte XORed with a fixed mask.
+struct MaskedLeaves(u8);
+
+impl LeafTransform for MaskedLeaves {
+    fn decode(&self, bytes: &mut Fr32Ary) {
+        bytes.iter_mut().for_each(|b| *b ^= self.0);
+    }
+
+    fn encode(&self, repr: &mut Fr32Ary) {
+        repr.iter_mut().for_each(|b| *b ^= self.0);
+    }
+}
+
+#[test]
+fn test_validate_replica_leaves_with_transform() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (data, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c: PoseidonDomain::random(rng),
+        comm_r_last: tree.root(),
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let challenges = [1, 17, 42];
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    let transform = MaskedLeaves(0x5a);
+    let masked: Vec<u8> = data
+        .chunks(NODE_SIZE)
+        .flat_map(|node| {
+            let fr = bytes_into_fr(node).expect("bytes_into_fr failure");
+            fr_into_bytes_with(&fr, &transform)
+        })
+        .collect();
+    assert_eq!(
+        bytes_into_fr_with(&masked[..NODE_SIZE], &transform).expect("bytes_into_fr_with failure"),
+        bytes_into_fr(&data[..NODE_SIZE]).expect("bytes_into_fr failure")
+    );
+
+    fallback::validate_replica_leaves_with(&proof.sectors[0], &challenges, &masked[..], &transform)
+        .expect("consistent masked replica rejected");
+
+    // Read as a standard replica, the masked leaves match none of the proofs.
+    assert!(
+        fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &masked[..]).is_err()
+    );
+}
+
+/// A `LeafSource` recording which nodes are read through it.
+struct RecordingSource<S> {
+    source: S,
+    nodes: RefCell<BTreeSet<usize>>,
+}
+
+impl<S: LeafSource> LeafSource for RecordingSource<S> {
+    fn read_node(&self, node: usize) -> Result<Vec<u8>> {
+        self.nodes.borrow_mut().insert(node);
+        self.source.read_node(node)
+    }
+}
+
+#[test]
+fn test_validate_replica_leaves_from_mmap() {
+    type Tree = LCTree<PoseidonHasher, U8, U2, U0>;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 4096,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let challenges = fallback::generate_leaf_challenges(
+        &pub_params,
+        fixture.pub_inputs.randomness,
+        0,
+        pub_params.challenge_count,
+    );
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    let replica_path = temp_dir.path().join("replica");
+    std::fs::write(&replica_path, &fixture.sectors[0].replica).expect("write failure");
+    let source = RecordingSource {
+        source: MmapLeafSource::open(&replica_path).expect("mmap failure"),
+        nodes: RefCell::new(BTreeSet::new()),
+    };
+    fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &source)
+        .expect("consistent replica file rejected");
+
+    // Exactly the challenged nodes are read from the map, so no other page is faulted in.
+    let challenged: BTreeSet<usize> = challenges.iter().map(|&c| c as usize).collect();
+    assert_eq!(*source.nodes.borrow(), challenged);
+}
+
+/// An in-memory replica standing in for a remote one, counting the leaves read from it.
+struct CountingReplica {
+    data: Vec<u8>,
+    reads: Cell<usize>,
+}
+
+impl LeafSource for CountingReplica {
+    fn read_node(&self, node: usize) -> Result<Vec<u8>> {
+        self.reads.set(self.reads.get() + 1);
+        self.data.read_node(node)
+    }
+}
+
+#[test]
+fn test_validate_replica_leaves_from_counting_replica() {
+    type Tree = LCTree<PoseidonHasher, U8, U2, U0>;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 4096,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let challenges = fallback::generate_leaf_challenges(
+        &pub_params,
+        fixture.pub_inputs.randomness,
+        0,
+        pub_params.challenge_count,
+    );
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    let source = CountingReplica {
+        data: fixture.sectors[0].replica.clone(),
+        reads: Cell::new(0),
+    };
+    fallback::validate_replica_leaves(&proof.sectors[0], &challenges, &source)
+        .expect("consistent replica rejected");
+
+    // Only the challenged leaves are read, never the whole sector.
+    assert_eq!(source.reads.get(), pub_params.challenge_count);
+}
+
+#[test]
+fn test_verify_partition_cover() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 1,
+        sector_count: 2,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let sectors: Vec<_> = (0..5u64)
+        .map(|id| PublicSector {
+            id: id.into(),
+            comm_r: PoseidonDomain::random(rng),
+        })
+        .collect();
+    let randomness = PoseidonDomain::random(rng);
+    let prover_id = PoseidonDomain::random(rng);
+
+    let partitions: Vec<_> = sectors
+        .chunks(pub_params.sector_count)
+        .enumerate()
+        .map(|(k, chunk)| fallback::PublicInputs {
+            randomness,
+            prover_id,
+            sectors: chunk.to_vec(),
+            k: Some(k),
+        })
+        .collect();
+    assert_eq!(partitions.len(), 3);
+    fallback::verify_partition_cover(&pub_params, &sectors, &partitions)
+        .expect("complete cover rejected");
+
+    // Dropping a partition leaves its sectors unchallenged.
+    let mut dropped = partitions.clone();
+    dropped.remove(1);
+    assert!(fallback::verify_partition_cover(&pub_params, &sectors, &dropped).is_err());
+
+    // So does proving one partition twice in place of another.
+    let mut repeated = partitions.clone();
+    repeated[1] = repeated[0].clone();
+    repeated[1].k = Some(1);
+    assert!(fallback::verify_partition_cover(&pub_params, &sectors, &repeated).is_err());
+
+    let mut reordered = partitions;
+    reordered.swap(0, 1);
+    assert!(fallback::verify_partition_cover(&pub_params, &sectors, &reordered).is_err());
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const UNREAD: AtomicBool = AtomicBool::new(false);
+/// Replica nodes read through `recording_read`, for the single test which reads through it.
+static REPLICA_NODES_READ: [AtomicBool; 4096] = [UNREAD; 4096];
+
+fn recording_read(start: usize, end: usize, buf: &mut [u8], replica: &File) -> Result<usize> {
+    for node in start / NODE_SIZE..(end + NODE_SIZE - 1) / NODE_SIZE {
+        REPLICA_NODES_READ[node].store(true, Ordering::SeqCst);
+    }
+    replica.read_exact_at(start as u64, &mut buf[..end - start])?;
+    Ok(end - start)
+}
+
+#[test]
+fn test_required_replica_ranges() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    // Large enough for a level cache tree to discard two rows, so segments are 512 leaves wide.
+    let leafs = REPLICA_NODES_READ.len();
+    let rows_to_discard = default_rows_to_discard(leafs, 8);
+    assert_eq!(rows_to_discard, 2);
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let temp_dir = tempdir().expect("tempdir failure");
+    let elements: Vec<PoseidonDomain> = (0..leafs).map(|_| PoseidonDomain::random(rng)).collect();
+    let data: Vec<u8> = elements
+        .iter()
+        .flat_map(|element| AsRef::<[u8]>::as_ref(element).to_vec())
+        .collect();
+    let replica_path = temp_dir.path().join("replica");
+    std::fs::write(&replica_path, &data).expect("replica write failure");
+
+    let config = StoreConfig::new(temp_dir.path(), "recording-lc-tree", rows_to_discard);
+    Tree::try_from_iter_with_config(elements.iter().map(|element| Ok(*element)), config.clone())
+        .expect("tree build failure");
+
+    // Reopen the tree, reading its replica through `recording_read`.
+    let reader = ExternalReader {
+        offset: 0,
+        source: File::open(&replica_path).expect("replica open failure"),
+        read_fn: recording_read,
+    };
+    let store = LCStore::new_from_disk_with_reader(
+        get_merkle_tree_len(leafs, 8).expect("tree len failure"),
+        8,
+        &config,
+        reader,
+    )
+    .expect("store open failure");
+    let tree = Tree::from_data_store(store, leafs).expect("tree open failure");
+
+    let priv_sectors = vec![PrivateSector::<Tree>::from_tree(
+        &tree,
+        PoseidonDomain::random(rng),
+    )];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    // Challenges 600 and 1000 share segment 1, which is adjacent to segment 0.
+    let challenges = [5, 600, 1000, 4095];
+    let required = fallback::required_replica_ranges::<Tree>(leafs, &challenges)
+        .expect("required_replica_ranges failure");
+    assert_eq!(
+        required.ranges,
+        vec![0..1024 * NODE_SIZE, 3584 * NODE_SIZE..4096 * NODE_SIZE]
+    );
+
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+
+    // The builder read exactly the nodes covered by the ranges.
+    let read: Vec<usize> = (0..leafs)
+        .filter(|&node| REPLICA_NODES_READ[node].load(Ordering::SeqCst))
+        .collect();
+    let covered: Vec<usize> = required
+        .ranges
+        .iter()
+        .flat_map(|range| range.start / NODE_SIZE..range.end / NODE_SIZE)
+        .collect();
+    assert_eq!(read, covered);
+
+    for ((inclusion_proof, &challenge), path_nodes) in proof.sectors[0]
+        .inclusion_proofs()
+        .iter()
+        .zip(&challenges)
+        .zip(&required.path_nodes)
+    {
+        let path = inclusion_proof.path();
+        assert_eq!(path_nodes.len(), path.len());
+        assert_eq!(path_nodes[0], challenge as usize);
+        for (level, (&node, (_, index))) in path_nodes.iter().zip(&path).enumerate() {
+            assert_eq!(node, challenge as usize / 8usize.pow(level as u32));
+            assert_eq!(node % 8, *index);
+        }
+    }
+}
+
+#[test]
+fn test_validate_submission() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 1,
+        sector_count: 2,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let deadline_sectors: Vec<_> = (0..5u64)
+        .map(|id| PublicSector {
+            id: id.into(),
+            comm_r: PoseidonDomain::random(rng),
+        })
+        .collect();
+    let randomness = PoseidonDomain::random(rng);
+    let prover_id = PoseidonDomain::random(rng);
+
+    let submission = fallback::PublicInputs {
+        randomness,
+        prover_id,
+        sectors: deadline_sectors[2..4].to_vec(),
+        k: Some(1),
+    };
+    let validate = |submission: &fallback::PublicInputs<PoseidonDomain>, k: usize| {
+        fallback::validate_submission(
+            &pub_params,
+            submission,
+            &deadline_sectors,
+            randomness,
+            prover_id,
+            k,
+        )
+    };
+    validate(&submission, 1).expect("valid submission rejected");
+
+    // A prover reusing the previous deadline's challenges.
+    let mut stale = submission.clone();
+    stale.randomness = PoseidonDomain::random(rng);
+    assert!(validate(&stale, 1).is_err());
+
+    // A submission made for another miner.
+    let mut other_prover = submission.clone();
+    other_prover.prover_id = PoseidonDomain::random(rng);
+    assert!(validate(&other_prover, 1).is_err());
+
+    assert!(validate(&submission, 0).is_err());
+
+    let mut wrong_sectors = submission;
+    wrong_sectors.sectors[1].comm_r = PoseidonDomain::random(rng);
+    assert!(validate(&wrong_sectors, 1).is_err());
+}
+
+#[test]
+fn test_public_sector_from_bytes() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let comm_r = PoseidonDomain::random(rng);
+
+    let bytes = <[u8; 32]>::try_from(&comm_r.into_bytes()[..]).expect("domain is 32 bytes");
+    let sector =
+        PublicSector::<PoseidonDomain>::from_bytes(7.into(), &bytes).expect("from_bytes failure");
+    assert_eq!(sector.id, SectorId::from(7));
+    assert_eq!(sector.comm_r, comm_r);
+
+    let err = PublicSector::<PoseidonDomain>::from_bytes(7.into(), &[0xff; 32])
+        .expect_err("non-canonical comm_r accepted");
+    assert!(matches!(
+        err.downcast_ref::<fr32::Error>(),
+        Some(fr32::Error::NonCanonical { top_bits_set: true })
+    ));
+}
+
+#[test]
+fn test_winning_post_request_round_trip() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+    let comm_c = PoseidonDomain::random(rng);
+    let comm_r_last = tree.root();
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let pub_inputs = fallback::PublicInputs {
+        randomness: PoseidonDomain::random(rng),
+        prover_id: PoseidonDomain::random(rng),
+        sectors: vec![PublicSector {
+            id: 7.into(),
+            comm_r: comm_r_from_parts::<PoseidonHasher>(CommC(comm_c), CommRLast(comm_r_last)),
+        }],
+        k: None,
+    };
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let proof =
+        FallbackPoSt::<Tree>::prove_all_partitions(&pub_params, &pub_inputs, &priv_inputs, 1)
+            .expect("proving failed");
+
+    // Verify against inputs which made the trip through the byte-encoded request.
+    let request = fallback::WinningPostRequest::from(&pub_inputs);
+    assert_eq!(request.sectors[0].sector_id, 7);
+    let decoded = fallback::PublicInputs::<PoseidonDomain>::try_from(request.clone())
+        .expect("request conversion failed");
+
+    let is_valid = FallbackPoSt::<Tree>::verify_all_partitions(&pub_params, &decoded, &proof)
+        .expect("verification failed");
+    assert!(is_valid);
+
+    // Bytes which do not encode a field element are rejected rather than reduced.
+    let mut invalid = request;
+    invalid.sectors[0].comm_r = [0xff; 32];
+    assert!(fallback::PublicInputs::<PoseidonDomain>::try_from(invalid).is_err());
+}
+
+#[test]
+fn test_proof_summary() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: 10,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+    let comm_c = PoseidonDomain::random(rng);
+    let comm_r_last = tree.root();
+    let comm_r = comm_r_from_parts::<PoseidonHasher>(CommC(comm_c), CommRLast(comm_r_last));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c,
+        comm_r_last,
+    }];
+    let pub_inputs = fallback::PublicInputs {
+        randomness: PoseidonDomain::random(rng),
+        prover_id: PoseidonDomain::random(rng),
+        sectors: vec![PublicSector {
+            id: 7.into(),
+            comm_r,
+        }],
+        k: Some(0),
+    };
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let proof = FallbackPoSt::<Tree>::prove(&pub_params, &pub_inputs, &priv_inputs)
+        .expect("proving failed");
+
+    let summary = proof.summary(&pub_inputs);
+    assert_eq!(
+        summary,
+        format!(
+            "sector_size=2048 partition=0 challenges_per_sector=10 sectors=[7:{}]",
+            hex::encode(comm_r.into_bytes())
+        )
+    );
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChallengeVector {
+    sector_size: u64,
+    randomness: String,
+    sector_id: u64,
+    /// Position of the sector among all challenged sectors; its challenges start at index
+    /// `k * challenge_count`.
+    k: u64,
+    challenge_count: usize,
+    challenges: Vec<u64>,
+}
+
+#[test]
+fn test_leaf_challenge_vectors() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/fallback_challenges.json");
+    let vectors: Vec<ChallengeVector> =
+        serde_json::from_slice(&std::fs::read(path).expect("failed to read challenge vectors"))
+            .expect("invalid challenge vectors");
+    assert!(!vectors.is_empty());
+
+    for vector in vectors {
+        let pub_params = fallback::PublicParams {
+            sector_size: vector.sector_size,
+            challenge_count: vector.challenge_count,
+            sector_count: 1,
+            api_version: ApiVersion::V1_1_0,
+        };
+        let randomness = PoseidonDomain::try_from_bytes(
+            &hex::decode(&vector.randomness).expect("invalid randomness hex"),
+        )
+        .expect("invalid randomness");
+
+        let first = vector.k * vector.challenge_count as u64;
+        let challenges: Vec<u64> = (first..first + vector.challenge_count as u64)
+            .map(|index| {
+                fallback::generate_leaf_challenge(&pub_params, randomness, vector.sector_id, index)
+            })
+            .collect();
+        assert_eq!(challenges, vector.challenges, "{:?}", vector);
+
+        if vector.k == 0 {
+            assert_eq!(
+                fallback::generate_leaf_challenges(
+                    &pub_params,
+                    randomness,
+                    vector.sector_id,
+                    vector.challenge_count
+                ),
+                vector.challenges
+            );
+        }
+    }
+}
+
+#[test]
+fn test_sectors_challenging_node() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 10,
+        sector_count: 3,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let mut randomness = [0u8; 32];
+    for (i, byte) in randomness.iter_mut().take(31).enumerate() {
+        *byte = i as u8;
+    }
+    let randomness = PoseidonDomain::try_from_bytes(&randomness).expect("invalid randomness");
+    let sector_ids: Vec<SectorId> = vec![0.into(), 7.into(), 42.into()];
+
+    let challenging =
+        |node| fallback::sectors_challenging_node(&pub_params, node, randomness, &sector_ids, 1);
+    assert_eq!(challenging(4), sector_ids);
+    assert_eq!(challenging(16), vec![SectorId::from(0), SectorId::from(7)]);
+    assert_eq!(challenging(35), vec![SectorId::from(0), SectorId::from(42)]);
+    assert!(challenging(0).is_empty());
+}
+
+#[test]
+fn test_generate_distinct_leaf_challenges() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 40,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let randomness = PoseidonDomain::from([1u8; 32]);
+
+    // 40 challenges over 64 nodes collide without rejection.
+    let plain = fallback::generate_leaf_challenges(&pub_params, randomness, 5, 40);
+    let mut unique = plain.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), 28);
+
+    let distinct = fallback::generate_distinct_leaf_challenges(&pub_params, randomness, 5, 40)
+        .expect("distinct challenges failure");
+    assert_eq!(distinct.len(), 40);
+    assert_eq!(&distinct[..10], &[7, 33, 28, 47, 60, 51, 54, 13, 21, 27]);
+    let mut unique = distinct.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), 40);
+    assert!(distinct.iter().all(|&challenge| challenge < 64));
+
+    // Every node of the sector can be challenged once, but not more.
+    assert_eq!(
+        fallback::generate_distinct_leaf_challenges(&pub_params, randomness, 5, 64)
+            .expect("distinct challenges failure")
+            .len(),
+        64
+    );
+    let err = fallback::generate_distinct_leaf_challenges(&pub_params, randomness, 5, 65)
+        .expect_err("more distinct challenges than nodes");
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::SectorTooSmall(64, 65))
+    ));
+}
+
+#[test]
+fn test_vanilla_proof_with_store() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c: PoseidonDomain::random(rng),
+        comm_r_last: tree.root(),
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let challenges = [1, 17, 42];
+    let rows_to_discard = Some(default_rows_to_discard(leaves, 8));
+    let mut store = HashMap::new();
+    for &challenge in &challenges[..2] {
+        store.insert(
+            challenge as usize,
+            tree.gen_proof(challenge as usize)
+                .expect("gen_proof failure"),
+        );
+    }
+
+    let fresh = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let stored =
+        fallback::vanilla_proof_with_store(SectorId::from(0), &priv_inputs, &challenges, &store)
+            .expect("vanilla proof with store failure");
+    assert_eq!(
+        serde_json::to_string(&stored).expect("serialize failure"),
+        serde_json::to_string(&fresh).expect("serialize failure")
+    );
+
+    // A stored proof for the wrong leaf is rejected rather than used.
+    store.insert(
+        42,
+        tree.gen_cached_proof(43, rows_to_discard)
+            .expect("gen_cached_proof failure"),
+    );
+    assert!(fallback::vanilla_proof_with_store(
+        SectorId::from(0),
+        &priv_inputs,
+        &challenges,
+        &store
+    )
+    .is_err());
+}
+
+#[test]
+fn test_sparse_replica_matches_full_replica() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64;
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c: PoseidonDomain::random(rng),
+        comm_r_last: tree.root(),
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let sector_id = SectorId::from(7);
+    let challenges = [3, 17, 42, 63];
+    let full = fallback::vanilla_proof(sector_id, &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let sparse = fallback::SparseReplica::from_tree(sector_id, &priv_sectors[0], &challenges)
+        .expect("sparse replica failure");
+
+    // Store the sparse replica offline and prove from it once the tree is gone.
+    let stored = serde_json::to_string(&sparse).expect("serialize failure");
+    drop(priv_inputs);
+    drop(priv_sectors);
+    drop(tree);
+    let sparse: fallback::SparseReplica<<Tree as MerkleTreeTrait>::Proof> =
+        serde_json::from_str(&stored).expect("deserialize failure");
+
+    let from_sparse = sparse
+        .vanilla_proof(sector_id, &challenges)
+        .expect("sparse vanilla proof failure");
+    assert_eq!(
+        serde_json::to_string(&from_sparse).expect("serialize failure"),
+        serde_json::to_string(&full).expect("serialize failure")
+    );
+
+    // Extracted challenges may be answered in any order, others not at all.
+    let reordered = sparse
+        .vanilla_proof(sector_id, &[42, 3])
+        .expect("sparse vanilla proof failure");
+    let leafs = full.sectors[0].leafs();
+    assert_eq!(reordered.sectors[0].leafs(), vec![leafs[2], leafs[0]]);
+    assert!(sparse.vanilla_proof(sector_id, &[4]).is_err());
+}
+
+#[test]
+fn test_sector_proof_compact_encoding() {
+    sector_proof_compact_encoding::<LCTree<PoseidonHasher, U8, U0, U0>>();
+    sector_proof_compact_encoding::<LCTree<PoseidonHasher, U8, U8, U2>>();
+}
+
+fn sector_proof_compact_encoding<Tree: 'static + MerkleTreeTrait>()
+where
+    Tree::Store: 'static,
+{
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let leaves = 64 * get_base_tree_count::<Tree>();
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector {
+        tree: &tree,
+        comm_c: <Tree::Hasher as Hasher>::Domain::random(rng),
+        comm_r_last: tree.root(),
+    }];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let challenges = [1, 17, 42, 63];
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let sector_proof = &proof.sectors[0];
+
+    let compact = sector_proof.encode_compact();
+    let decoded = fallback::SectorProof::<Tree::Proof>::decode_compact(&compact, leaves)
+        .expect("decode failure");
+    assert_eq!(
+        serde_json::to_string(&decoded).expect("serialize failure"),
+        serde_json::to_string(sector_proof).expect("serialize failure")
+    );
+    assert!(decoded
+        .inclusion_proofs()
+        .iter()
+        .zip(&challenges)
+        .all(|(proof, &challenge)| proof.validate(challenge as usize)));
+
+    let bincoded = bincode::serialize(sector_proof).expect("bincode failure");
+    assert!(
+        compact.len() < bincoded.len(),
+        "compact {} bytes, bincode {} bytes",
+        compact.len(),
+        bincoded.len()
+    );
+
+    assert!(fallback::SectorProof::<Tree::Proof>::decode_compact(
+        &compact[..compact.len() - 1],
+        leaves
+    )
+    .is_err());
+}
+
+#[test]
+fn test_compressed_proof_set() {
+    // A 32 KiB sector.
+    type Tree = LCTree<PoseidonHasher, U8, U2, U0>;
+    let leaves = 1024;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, leaves, Some(temp_dir.path().to_path_buf()));
+
+    let priv_sectors = vec![PrivateSector::<Tree>::from_tree(
+        &tree,
+        PoseidonDomain::random(rng),
+    )];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+
+    let pub_params = fallback::PublicParams {
+        sector_size: (leaves * NODE_SIZE) as u64,
+        challenge_count: 16,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let challenges = fallback::generate_leaf_challenges(
+        &pub_params,
+        PoseidonDomain::random(rng),
+        0,
+        pub_params.challenge_count,
+    );
+    let proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let inclusion_proofs = proof.sectors[0].inclusion_proofs();
+
+    let compressed =
+        fallback::CompressedProofSet::<<Tree as MerkleTreeTrait>::Proof>::new(inclusion_proofs)
+            .expect("compression failure");
+    let expanded = compressed.expand().expect("expansion failure");
+    assert_eq!(
+        serde_json::to_string(&expanded).expect("serialize failure"),
+        serde_json::to_string(inclusion_proofs).expect("serialize failure")
+    );
+    assert!(expanded
+        .iter()
+        .zip(&challenges)
+        .all(|(proof, &challenge)| proof.validate(challenge as usize)));
+
+    let naive_len = bincode::serialize(inclusion_proofs)
+        .expect("bincode failure")
+        .len();
+    let compressed_len = bincode::serialize(&compressed)
+        .expect("bincode failure")
+        .len();
+    assert!(
+        compressed_len < naive_len,
+        "compressed {} bytes, naive {} bytes",
+        compressed_len,
+        naive_len
+    );
+}
+
+#[test]
+fn test_generate_leaf_challenges_par() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 1024 * NODE_SIZE as u64,
+        challenge_count: 5000,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let randomness = PoseidonDomain::from([3u8; 32]);
+
+    assert_eq!(
+        fallback::generate_leaf_challenges_par(&pub_params, randomness, 11, 5000),
+        fallback::generate_leaf_challenges(&pub_params, randomness, 11, 5000)
+    );
+}
+
+#[test]
+fn test_generate_leaf_challenges_bulk() {
+    let pub_params = fallback::PublicParams {
+        sector_size: 1024 * NODE_SIZE as u64,
+        challenge_count: 66,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let randomness = PoseidonDomain::from([5u8; 32]);
+    let sector_ids: Vec<u64> = (0..10).map(|i| i * 7 + 3).collect();
+
+    let bulk = fallback::generate_leaf_challenges_bulk(&pub_params, randomness, &sector_ids, 66);
+    assert_eq!(bulk.len(), sector_ids.len());
+    for (challenges, &sector_id) in bulk.iter().zip(&sector_ids) {
+        assert_eq!(
+            challenges,
+            &fallback::generate_leaf_challenges(&pub_params, randomness, sector_id, 66)
+        );
+    }
+}
+
+#[test]
+fn test_private_sector_from_tree() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_data, tree) = generate_tree::<Tree, _>(rng, 64, Some(temp_dir.path().to_path_buf()));
+    let comm_c = <PoseidonHasher as Hasher>::Domain::random(rng);
+
+    let explicit = PrivateSector::<Tree> {
+        tree: &tree,
+        comm_c,
+        comm_r_last: tree.root(),
+    };
+    let derived = PrivateSector::<Tree>::from_tree(&tree, comm_c);
+
+    assert_eq!(derived.comm_r_last, explicit.comm_r_last);
+    assert_eq!(
+        derived.comm_r(),
+        comm_r_from_parts::<PoseidonHasher>(CommC(comm_c), CommRLast(explicit.comm_r_last))
+    );
+}
+
+#[test]
+fn test_winning_fixture_proves() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 66,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+    assert_eq!(fixture.sectors[0].replica.len(), 64 * NODE_SIZE);
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = FallbackPoSt::<Tree>::prove(&pub_params, &fixture.pub_inputs, &priv_inputs)
+        .expect("proving failed");
+    assert!(
+        FallbackPoSt::<Tree>::verify(&pub_params, &fixture.pub_inputs, &proof)
+            .expect("verification failed")
+    );
+
+    // The same seed reproduces the same sectors.
+    let other_dir = tempdir().expect("tempdir failure");
+    let again =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(other_dir.path().into()));
+    assert_eq!(again.sectors[0].replica, fixture.sectors[0].replica);
+    assert_eq!(
+        again.pub_inputs.sectors[0].comm_r,
+        fixture.pub_inputs.sectors[0].comm_r
+    );
+}
+
+#[test]
+fn test_sector_proof_validate_canonical() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 5,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proof = FallbackPoSt::<Tree>::prove(&pub_params, &fixture.pub_inputs, &priv_inputs)
+        .expect("proving failed");
+    let mut sector_proof = proof.sectors[0].clone();
+    sector_proof
+        .validate_canonical()
+        .expect("canonical proof rejected");
+
+    // An untrusted sender replaces a sibling with bytes above the field modulus.
+    let inclusion_proof = &sector_proof.inclusion_proofs[1];
+    let mut path = inclusion_proof.path();
+    path[1].0[2] = PoseidonDomain([0xff; 32]);
+    let tampered = MerkleProof::from_parts(inclusion_proof.leaf(), inclusion_proof.root(), &path)
+        .expect("from_parts failure");
+    sector_proof.inclusion_proofs[1] = tampered;
+
+    let bytes = bincode::serialize(&sector_proof).expect("serialize failure");
+    let received: fallback::SectorProof<<Tree as MerkleTreeTrait>::Proof> =
+        bincode::deserialize(&bytes).expect("deserialize failure");
+
+    let err = received
+        .validate_canonical()
+        .expect_err("non-canonical sibling accepted");
+    match err.downcast_ref::<Error>() {
+        Some(Error::NonCanonicalElement(what)) => {
+            assert_eq!(what, "sibling 2 at level 1 of inclusion proof 1")
+        }
+        _ => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_verify_batch() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 66,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let proofs = FallbackPoSt::<Tree>::prove_all_partitions(
+        &pub_params,
+        &fixture.pub_inputs,
+        &priv_inputs,
+        1,
+    )
+    .expect("proving failed");
+
+    // Proofs 2 and 4 are checked against randomness they were not generated for.
+    let mut pub_inputs = vec![fixture.pub_inputs.clone(); 5];
+    for &i in &[2, 4] {
+        pub_inputs[i].randomness = PoseidonDomain::random(rng);
+    }
+    let batch = vec![proofs; 5];
+
+    assert_eq!(
+        fallback::verify_batch::<Tree>(&pub_params, &pub_inputs, &batch, true)
+            .expect("verify batch failure"),
+        vec![2]
+    );
+    assert_eq!(
+        fallback::verify_batch::<Tree>(&pub_params, &pub_inputs, &batch, false)
+            .expect("verify batch failure"),
+        vec![2, 4]
+    );
+    assert!(
+        fallback::verify_batch::<Tree>(&pub_params, &pub_inputs[..2], &batch[..2], true)
+            .expect("verify batch failure")
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_sector_proof_derived_comm_r() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 10,
+        sector_count: 2,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let temp_dir = tempdir().expect("tempdir failure");
+    let fixture =
+        fixtures::winning_fixture::<Tree>(TEST_SEED, &pub_params, Some(temp_dir.path().into()));
+
+    let priv_sectors = fixture.priv_sectors();
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let mut proof = FallbackPoSt::<Tree>::prove(&pub_params, &fixture.pub_inputs, &priv_inputs)
+        .expect("proving failed");
+
+    for (sector_proof, pub_sector) in proof.sectors.iter().zip(&fixture.pub_inputs.sectors) {
+        assert_eq!(sector_proof.derived_comm_r(), pub_sector.comm_r);
+    }
+
+    proof.sectors[1].comm_c = CommC(PoseidonDomain::random(rng));
+    assert_ne!(
+        proof.sectors[1].derived_comm_r(),
+        fixture.pub_inputs.sectors[1].comm_r
+    );
+}
+
+#[test]
+fn test_winning_fixture_derived_comm_c() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let pub_params = fallback::PublicParams {
+        sector_size: 64 * NODE_SIZE as u64,
+        challenge_count: 66,
+        sector_count: 1,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let fixture = |temp_path: &std::path::Path| {
+        fixtures::winning_fixture_with::<Tree>(
+            TEST_SEED,
+            &pub_params,
+            Some(temp_path.into()),
+            fixtures::CommCMode::DerivedFromRoot,
+        )
+    };
+
+    let temp_dir = tempdir().expect("tempdir failure");
+    let derived = fixture(temp_dir.path());
+    let root = derived.sectors[0].tree.root();
+    assert_eq!(
+        derived.sectors[0].comm_c,
+        <PoseidonHasher as Hasher>::Function::hash(AsRef::<[u8]>::as_ref(&root))
+    );
+    assert_eq!(
+        derived.pub_inputs.sectors[0].comm_r,
+        comm_r_from_parts::<PoseidonHasher>(CommC(derived.sectors[0].comm_c), CommRLast(root))
+    );
+
+    let other_dir = tempdir().expect("tempdir failure");
+    assert_eq!(
+        fixture(other_dir.path()).pub_inputs.sectors[0].comm_r,
+        derived.pub_inputs.sectors[0].comm_r
+    );
+}
@@ -8,7 +8,7 @@ use generic_array::typenum::{U0, U2, U8};
 use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 use storage_proofs_core::{
-    merkle::{generate_tree, get_base_tree_count, LCTree, MerkleTreeTrait},
+    merkle::{generate_tree, get_base_tree_count, LCTree, MerkleProofTrait, MerkleTreeTrait},
     proof::ProofScheme,
     sector::OrderedSectorSet,
     TEST_SEED,
@@ -119,6 +119,20 @@ where
     let proof = RationalPoSt::<Tree>::prove(&pub_params, &pub_inputs, &priv_inputs)
         .expect("proving failed");
 
+    // Proofs are generated in parallel, but must come back in challenge order.
+    let (serial_leafs, serial_paths): (Vec<_>, Vec<_>) = pub_inputs
+        .challenges
+        .iter()
+        .map(|challenge| {
+            let proof = trees[&challenge.sector]
+                .gen_cached_proof(challenge.leaf as usize, None)
+                .expect("gen_cached_proof failure");
+            (proof.leaf(), proof.path())
+        })
+        .unzip();
+    assert_eq!(proof.leafs(), serial_leafs);
+    assert_eq!(proof.paths(), serial_paths);
+
     let is_valid = RationalPoSt::<Tree>::verify(&pub_params, &pub_inputs, &proof)
         .expect("verification failed");
 
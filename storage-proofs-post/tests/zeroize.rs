@@ -0,0 +1,122 @@
+#![cfg(feature = "zeroize")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::mem::{self, ManuallyDrop};
+use std::ptr;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use blstrs::Scalar as Fr;
+use ff::Field;
+use filecoin_hashers::{
+    poseidon::{PoseidonDomain, PoseidonHasher},
+    Domain, Hasher,
+};
+use generic_array::typenum::{U0, U8};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs_core::{
+    merkle::{generate_tree, LCTree, MerkleProofTrait},
+    sector::SectorId,
+    TEST_SEED,
+};
+use storage_proofs_post::fallback::{self, PrivateSector, PublicSector, Sector};
+use tempfile::tempdir;
+
+/// Copies out the contents of the watched allocation as it is freed, so that a test can inspect
+/// what a dropped value left behind without reading freed memory.
+struct InspectingAllocator;
+
+static WATCHED: AtomicUsize = AtomicUsize::new(0);
+static FREED_LEN: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_BYTE: AtomicU8 = AtomicU8::new(0);
+static FREED: [AtomicU8; 4096] = [ZERO_BYTE; 4096];
+
+unsafe impl GlobalAlloc for InspectingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if ptr as usize == WATCHED.load(Ordering::SeqCst) {
+            let len = layout.size().min(FREED.len());
+            for (i, byte) in FREED[..len].iter().enumerate() {
+                byte.store(*ptr.add(i), Ordering::SeqCst);
+            }
+            FREED_LEN.store(len, Ordering::SeqCst);
+            WATCHED.store(0, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: InspectingAllocator = InspectingAllocator;
+
+/// Drops `value` and returns the elements the allocation at `elements` held when it was freed.
+/// They are wrapped in `ManuallyDrop` since any memory they owned has been freed as well.
+fn drop_and_inspect<V, T>(value: V, elements: *const T) -> Vec<ManuallyDrop<T>> {
+    WATCHED.store(elements as usize, Ordering::SeqCst);
+    drop(value);
+    assert_eq!(
+        WATCHED.load(Ordering::SeqCst),
+        0,
+        "allocation was not freed"
+    );
+
+    let freed: Vec<u8> = FREED[..FREED_LEN.load(Ordering::SeqCst)]
+        .iter()
+        .map(|byte| byte.load(Ordering::SeqCst))
+        .collect();
+    freed
+        .chunks_exact(mem::size_of::<T>())
+        .map(|element| ManuallyDrop::new(unsafe { ptr::read_unaligned(element.as_ptr().cast()) }))
+        .collect()
+}
+
+// A single test, since the allocator watches one allocation at a time.
+#[test]
+fn test_zeroize_on_drop() {
+    type Tree = LCTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let temp_dir = tempdir().expect("tempdir failure");
+    let (_, tree) = generate_tree::<Tree, _>(rng, 64, Some(temp_dir.path().to_path_buf()));
+    let zero = <PoseidonHasher as Hasher>::Domain::default();
+
+    let priv_sectors = vec![PrivateSector::<Tree>::from_tree(
+        &tree,
+        PoseidonDomain::random(rng),
+    )];
+    let priv_inputs = fallback::PrivateInputs::<Tree> {
+        sectors: &priv_sectors,
+    };
+    let challenges = [1, 17, 42];
+    let mut proof = fallback::vanilla_proof(SectorId::from(0), &priv_inputs, &challenges)
+        .expect("vanilla proof failure");
+    let sector_proof = proof.sectors.remove(0);
+
+    let pub_sector = PublicSector {
+        id: SectorId::from(0),
+        comm_r: priv_sectors[0].comm_r(),
+    };
+    let sector = Sector::<Tree>::circuit(&pub_sector, &sector_proof).expect("circuit failure");
+
+    let leafs_ptr = sector.leafs.as_ptr();
+    let leafs = drop_and_inspect(sector, leafs_ptr);
+    assert_eq!(leafs.len(), challenges.len());
+    assert!(leafs.iter().all(|leaf| **leaf == Some(Fr::zero())));
+
+    let proofs_ptr = sector_proof.inclusion_proofs.as_ptr();
+    let proofs = drop_and_inspect(sector_proof, proofs_ptr);
+    assert_eq!(proofs.len(), challenges.len());
+    assert!(proofs
+        .iter()
+        .all(|proof| proof.leaf() == zero && proof.root() == zero));
+
+    let sectors_ptr = priv_sectors.as_ptr();
+    let sectors = drop_and_inspect(priv_sectors, sectors_ptr);
+    assert_eq!(sectors.len(), 1);
+    assert_eq!(sectors[0].comm_c, zero);
+    assert_eq!(sectors[0].comm_r_last, zero);
+}